@@ -1,14 +1,38 @@
 #![no_std]
 #[cfg(test)]
 mod test;
-use soroban_sdk::{contract, contractimpl, token, Address, Env, Vec};
-use vestroll_common::{Payment, VaultError};
+use soroban_sdk::{contract, contractimpl, contracttype, token, Address, BytesN, Env, Vec};
+use vestroll_common::{
+    complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm, Payment, VaultError,
+};
+
+/// Storage schema version this code expects.
+const SCHEMA_VERSION: u32 = 1;
 
 #[contract]
 pub struct BatchPayoutContract;
 
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Admin,
+}
+
 #[contractimpl]
 impl BatchPayoutContract {
+    /// Sets the admin allowed to upgrade this contract. Payouts themselves
+    /// need no admin.
+    pub fn initialize(env: Env, admin: Address) -> Result<(), VaultError> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(VaultError::NotAuthorized);
+        }
+
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        set_schema_version(&env, SCHEMA_VERSION);
+        Ok(())
+    }
+
     /// Iterates through a list of payments and transfers the specified asset to each recipient.
     /// This contract must have the required asset balance before invocation.
     pub fn process(env: Env, asset: Address, payments: Vec<Payment>) -> Result<(), VaultError> {
@@ -36,4 +60,32 @@ impl BatchPayoutContract {
 
         Ok(())
     }
+
+    /// Fetches the admin address
+    pub fn admin(env: Env) -> Result<Address, VaultError> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .ok_or(VaultError::AdminNotSet)
+    }
+
+    /// Admin replaces the contract's code. Run `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), VaultError> {
+        upgrade_wasm(&env, &Self::admin(env.clone())?, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Admin stamps storage with schema version 1. The batch contract keeps
+    /// nothing but its admin, so there is no data to move.
+    pub fn migrate(env: Env) -> Result<u32, VaultError> {
+        let from = pending_migration(&env, &Self::admin(env.clone())?, SCHEMA_VERSION)
+            .ok_or(VaultError::AlreadyMigrated)?;
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    /// Returns the storage schema version of this deployment.
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
 }
@@ -1,7 +1,7 @@
 #![cfg(test)]
 
 use crate::{BatchPayoutContract, BatchPayoutContractClient};
-use soroban_sdk::{testutils::Address as _, token, Address, Env, Vec};
+use soroban_sdk::{testutils::Address as _, token, Address, BytesN, Env, Vec};
 use vestroll_common::{Payment, VaultError};
 
 fn create_token_contract<'a>(env: &Env, admin: &Address) -> token::Client<'a> {
@@ -92,3 +92,23 @@ fn test_insufficient_contract_balance() {
     let result = client.try_process(&token.address, &payments);
     assert_eq!(result, Err(Ok(VaultError::InsufficientBalance)));
 }
+
+#[test]
+fn test_upgrade_path_requires_initialized_admin() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(BatchPayoutContract, ());
+    let client = BatchPayoutContractClient::new(&env, &contract_id);
+    let wasm_hash = BytesN::from_array(&env, &[0; 32]);
+
+    assert_eq!(client.try_upgrade(&wasm_hash), Err(Ok(VaultError::AdminNotSet)));
+    assert_eq!(client.version(), 0);
+
+    let admin = Address::generate(&env);
+    client.initialize(&admin);
+    assert_eq!(client.try_initialize(&admin), Err(Ok(VaultError::NotAuthorized)));
+    assert_eq!(client.admin(), admin);
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(VaultError::AlreadyMigrated)));
+}
@@ -1,5 +1,5 @@
 #![no_std]
-use soroban_sdk::{contracterror, contracttype, symbol_short, Address, Symbol};
+use soroban_sdk::{contracterror, contracttype, symbol_short, Address, BytesN, Env, Symbol, Vec};
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -7,6 +7,7 @@ pub enum ContractType {
     FixedRate,
     Milestone,
     PayAsYouGo,
+    Streaming,
 }
 
 #[contracttype]
@@ -16,6 +17,9 @@ pub enum ContractStatus {
     Active,
     Completed,
     Cancelled,
+    Disputed,
+    Expired,
+    OnHold,
 }
 
 #[contracttype]
@@ -38,6 +42,25 @@ pub struct TreasuryStats {
     pub total_liquidity: i128,
 }
 
+/// Funds the vault holds back for a single lifecycle contract. `reserved` is
+/// everything ever set aside for it; `spent` is what has been paid out.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reservation {
+    pub asset: Address,
+    pub reserved: i128,
+    pub spent: i128,
+}
+
+/// Protocol fee charged on payouts in one asset: `bps` basis points of the
+/// gross amount paid, but never less than `min_fee`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfig {
+    pub bps: u32,
+    pub min_fee: i128,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PayoutEntry {
@@ -46,6 +69,15 @@ pub struct PayoutEntry {
     pub asset: Address,
 }
 
+/// One payout out of a lifecycle contract's reservation, possibly split
+/// between several recipients, as batched by a payroll run.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReservedPayout {
+    pub contract_id: u32,
+    pub payments: Vec<Payment>,
+}
+
 #[contracttype]
 #[derive(Clone)]
 pub enum DataKey {
@@ -58,6 +90,10 @@ pub enum DataKey {
     TotalLocked(Address),
     TotalFees(Address),
     Token,
+    Lifecycle,
+    Reservation(u32),
+    TotalReserved(Address),
+    FeeConfig(Address),
 }
 
 // Error
@@ -79,6 +115,10 @@ pub enum VaultError {
     MissingTrustline = 12,
     BatchEmptyList = 13,
     BatchPayoutFailed = 14,
+    InsufficientReservedFunds = 15,
+    ReservationNotFound = 16,
+    ReservationAssetMismatch = 17,
+    AlreadyMigrated = 18,
 }
 
 #[contracterror]
@@ -101,8 +141,50 @@ pub enum LifecycleError {
     EmployeeCannotReceivePayment = 14,
     EmployeeProfileNotFound = 15,
     VaultPaymentFailed = 16,
+    InvalidSchedule = 17,
+    NoPaymentDue = 18,
+    ContractNotDraft = 19,
+    OfferExpired = 20,
+    AmendmentNotFound = 21,
+    AmendmentPending = 22,
+    InvalidAmendment = 23,
+    MilestoneNotSubmitted = 24,
+    MilestoneAlreadySubmitted = 25,
+    DisputeNotFound = 26,
+    InvalidAmount = 27,
+    InsufficientVaultFunds = 28,
+    ContractNotExpired = 29,
+    ContractNotOnHold = 30,
+    HourlyRateNotSet = 31,
+    TimesheetNotFound = 32,
+    InvalidTimesheetState = 33,
+    UsageTermsNotSet = 34,
+    UsageReportNotFound = 35,
+    DuplicateUsageReport = 36,
+    ChallengeWindowOpen = 37,
+    ChallengeWindowClosed = 38,
+    AlreadyMigrated = 39,
+    InvalidSplitRule = 40,
+    ClaimNotFound = 41,
+    InvalidClaimState = 42,
+    NoPendingAdvance = 43,
+    AdvanceLimitExceeded = 44,
+    OperatorCapExceeded = 45,
+    InvalidPayrollRun = 46,
+    KeeperPaymentsDisabled = 47,
+    PayrollRunNotFound = 48,
+    InvalidPayrollRunState = 49,
+    MigrationPending = 50,
 }
 
+// Storage TTL policy, in ledgers (~5s each). Contracts keep their instance
+// and per-record persistent entries alive by extending them to the `_AMOUNT`
+// whenever less than the `_THRESHOLD` remains.
+pub const DAY_IN_LEDGERS: u32 = 17_280;
+pub const INSTANCE_BUMP_THRESHOLD: u32 = 7 * DAY_IN_LEDGERS;
+pub const INSTANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+pub const RECORD_BUMP_THRESHOLD: u32 = 30 * DAY_IN_LEDGERS;
+pub const RECORD_BUMP_AMOUNT: u32 = 120 * DAY_IN_LEDGERS;
 
 
 // Events
@@ -118,3 +200,53 @@ pub struct Payment {
 pub const PAYOUT: Symbol = symbol_short!("payout");
 pub const BATCH_DONE: Symbol = symbol_short!("batch");
 pub const INVOICE: Symbol = symbol_short!("invoice");
+pub const RESERVED: Symbol = symbol_short!("reserved");
+pub const RELEASED: Symbol = symbol_short!("released");
+pub const FEE: Symbol = symbol_short!("fee");
+pub const FEES_OUT: Symbol = symbol_short!("fees_out");
+
+// Upgrades
+pub const UPGRADED: Symbol = symbol_short!("upgraded");
+pub const MIGRATED: Symbol = symbol_short!("migrated");
+
+/// Instance keys shared by every contract's upgrade path. Kept apart from the
+/// per-contract `DataKey`s so they never collide with contract storage.
+#[contracttype]
+#[derive(Clone)]
+pub enum UpgradeKey {
+    SchemaVersion,
+}
+
+/// Storage schema version the running deployment has been migrated to.
+/// Deployments that predate versioning report 0.
+pub fn schema_version(env: &Env) -> u32 {
+    env.storage().instance().get(&UpgradeKey::SchemaVersion).unwrap_or(0)
+}
+
+pub fn set_schema_version(env: &Env, version: u32) {
+    env.storage().instance().set(&UpgradeKey::SchemaVersion, &version);
+}
+
+/// Requires `admin`'s auth, then swaps the contract's code for
+/// `new_wasm_hash` and emits it. The code being replaced cannot be read from
+/// inside the contract, so the event carries only the new hash.
+pub fn upgrade_wasm(env: &Env, admin: &Address, new_wasm_hash: BytesN<32>) {
+    admin.require_auth();
+    env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+
+    env.events().publish((UPGRADED,), new_wasm_hash);
+}
+
+/// Requires `admin`'s auth and returns the stored schema version when it is
+/// behind `to`, or `None` once storage is already at `to`.
+pub fn pending_migration(env: &Env, admin: &Address, to: u32) -> Option<u32> {
+    admin.require_auth();
+    let from = schema_version(env);
+    (from < to).then_some(from)
+}
+
+/// Records that storage has been migrated from `from` to `to`.
+pub fn complete_migration(env: &Env, from: u32, to: u32) {
+    set_schema_version(env, to);
+    env.events().publish((MIGRATED,), (from, to));
+}
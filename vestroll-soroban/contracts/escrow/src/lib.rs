@@ -3,9 +3,13 @@
 #[cfg(test)]
 mod test;
 
-use soroban_sdk::{contract, contractimpl, token, Address, Env};
+use soroban_sdk::{contract, contractimpl, token, Address, BytesN, Env};
+use vestroll_common::{complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm};
 use vestroll_common::VaultError; // Reuse the VaultError for common vault behaviors
 
+/// Storage schema version this code expects.
+const SCHEMA_VERSION: u32 = 1;
+
 #[contract]
 pub struct EscrowContract;
 
@@ -31,6 +35,7 @@ impl EscrowContract {
         env.storage()
             .instance()
             .set(&DataKey::TotalReleased, &0i128);
+        set_schema_version(&env, SCHEMA_VERSION);
     }
 
     /// Fetches the admin address
@@ -128,4 +133,25 @@ impl EscrowContract {
 
         Ok(())
     }
+
+    /// Admin replaces the escrow's code, keeping its storage. Run `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), VaultError> {
+        let admin: Address = Self::admin(env.clone())?;
+        upgrade_wasm(&env, &admin, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Admin stamps storage with schema version 1. Escrow storage is laid out
+    /// as before versioning, so nothing is moved.
+    pub fn migrate(env: Env) -> Result<u32, VaultError> {
+        let admin: Address = Self::admin(env.clone())?;
+        let from = pending_migration(&env, &admin, SCHEMA_VERSION).ok_or(VaultError::AlreadyMigrated)?;
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    /// Returns the storage schema version of this deployment.
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
 }
@@ -3,7 +3,7 @@ use soroban_sdk::{
     testutils::{Address as _, MockAuth, MockAuthInvoke},
     token, Address, Env, IntoVal,
 };
-use vestroll_common::VaultError;
+use vestroll_common::{UpgradeKey, VaultError};
 
 fn create_token_contract<'a>(env: &Env, admin: &Address) -> token::Client<'a> {
     let contract_id = env.register_stellar_asset_contract_v2(admin.clone());
@@ -167,3 +167,22 @@ fn test_over_release() {
     let res = client.try_release_milestone(&1, &recipient, &600);
     assert_eq!(res, Err(Ok(VaultError::InsufficientBalance)));
 }
+
+#[test]
+fn test_migrate_runs_once_per_version() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let token = create_token_contract(&env, &Address::generate(&env));
+    let contract_id = env.register(EscrowContract, ());
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &token.address);
+
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(VaultError::AlreadyMigrated)));
+
+    env.as_contract(&contract_id, || env.storage().instance().remove(&UpgradeKey::SchemaVersion));
+    assert_eq!(client.migrate(), 1);
+    assert_eq!(client.version(), 1);
+}
@@ -1,9 +1,16 @@
 #![no_std]
 
+#[cfg(test)]
+mod test;
+
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, panic_with_error, Address, Env, Symbol,
-    Vec,
+    contract, contracterror, contractimpl, contracttype, panic_with_error, Address, BytesN, Env,
+    Symbol, Vec,
 };
+use vestroll_common::{complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm};
+
+/// Storage schema version this code expects.
+const SCHEMA_VERSION: u32 = 1;
 
 // Storage keys as per your format
 #[derive(Clone)]
@@ -14,6 +21,7 @@ pub enum DataKey {
     Managers,
     Initialized,
     ManagerList,
+    Delegation(Address, Address),
 }
 
 // Error types as per your format
@@ -29,6 +37,39 @@ pub enum ContractError {
     TransferToSelf = 7,
     NotProposedAdmin = 8,
     Unauthorized = 9,
+    AlreadyMigrated = 10,
+    InvalidDelegation = 11,
+    DelegationNotFound = 12,
+    SelfDelegation = 13,
+}
+
+/// Rights a principal can delegate to an operator
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OperatorScope {
+    ProcessPayments,
+    CompleteMilestones,
+    CreateContracts,
+}
+
+/// Rights a principal (usually an employer) has handed to an operator.
+/// `cap` limits the total value the operator may move on the principal's
+/// behalf; contracts that honor the delegation track it against `granted_at`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Delegation {
+    pub scopes: Vec<OperatorScope>,
+    pub cap: Option<i128>,
+    pub expires_at: Option<u64>,
+    pub granted_at: u64,
+}
+
+/// Whether a delegation lets its operator act within `scope` right now.
+/// Contracts that honor delegations check them through this, so they agree
+/// with `is_operator` on when one has expired.
+pub fn check_operator(env: &Env, delegation: &Delegation, scope: &OperatorScope) -> bool {
+    let expired = matches!(delegation.expires_at, Some(expires_at) if env.ledger().timestamp() >= expires_at);
+    !expired && delegation.scopes.contains(scope)
 }
 
 // Events - using your contracttype format
@@ -61,6 +102,19 @@ pub struct ManagerRemovedEvent {
     pub admin: Address,
 }
 
+#[contracttype]
+pub struct OperatorGrantedEvent {
+    pub principal: Address,
+    pub operator: Address,
+    pub delegation: Delegation,
+}
+
+#[contracttype]
+pub struct OperatorRevokedEvent {
+    pub principal: Address,
+    pub operator: Address,
+}
+
 #[contract]
 pub struct IdentityManagementContract;
 
@@ -77,6 +131,7 @@ impl IdentityManagementContract {
 
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Initialized, &true);
+        set_schema_version(&env, SCHEMA_VERSION);
 
         let empty_managers: Vec<Address> = Vec::new(&env);
         env.storage()
@@ -312,6 +367,131 @@ impl IdentityManagementContract {
         Ok(managers.len())
     }
 
+    /// Delegate scoped rights to an operator (Principal only)
+    /// Granting again replaces the previous delegation
+    pub fn grant_operator(
+        env: Env,
+        principal: Address,
+        operator: Address,
+        scopes: Vec<OperatorScope>,
+        cap: Option<i128>,
+        expires_at: Option<u64>,
+    ) -> Result<(), ContractError> {
+        Self::require_initialized(&env)?;
+        principal.require_auth();
+
+        if principal == operator {
+            panic_with_error!(&env, ContractError::SelfDelegation);
+        }
+
+        let now = env.ledger().timestamp();
+        if scopes.is_empty()
+            || matches!(cap, Some(cap) if cap <= 0)
+            || matches!(expires_at, Some(expires_at) if expires_at <= now)
+        {
+            panic_with_error!(&env, ContractError::InvalidDelegation);
+        }
+
+        let delegation = Delegation {
+            scopes,
+            cap,
+            expires_at,
+            granted_at: now,
+        };
+        env.storage().persistent().set(
+            &DataKey::Delegation(principal.clone(), operator.clone()),
+            &delegation,
+        );
+
+        env.events().publish(
+            (Symbol::new(&env, "operator_granted"),),
+            OperatorGrantedEvent {
+                principal,
+                operator,
+                delegation,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw every right delegated to an operator (Principal only)
+    pub fn revoke_operator(
+        env: Env,
+        principal: Address,
+        operator: Address,
+    ) -> Result<(), ContractError> {
+        Self::require_initialized(&env)?;
+        principal.require_auth();
+
+        let key = DataKey::Delegation(principal.clone(), operator.clone());
+        if !env.storage().persistent().has(&key) {
+            panic_with_error!(&env, ContractError::DelegationNotFound);
+        }
+        env.storage().persistent().remove(&key);
+
+        env.events().publish(
+            (Symbol::new(&env, "operator_revoked"),),
+            OperatorRevokedEvent {
+                principal,
+                operator,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Get what a principal has delegated to an operator, expired or not
+    pub fn get_delegation(
+        env: Env,
+        principal: Address,
+        operator: Address,
+    ) -> Result<Option<Delegation>, ContractError> {
+        Self::require_initialized(&env)?;
+
+        Ok(env
+            .storage()
+            .persistent()
+            .get(&DataKey::Delegation(principal, operator)))
+    }
+
+    /// Check if an operator currently holds a scope for a principal
+    pub fn is_operator(
+        env: Env,
+        principal: Address,
+        operator: Address,
+        scope: OperatorScope,
+    ) -> Result<bool, ContractError> {
+        let delegation = Self::get_delegation(env.clone(), principal, operator)?;
+
+        Ok(delegation.is_some_and(|delegation| check_operator(&env, &delegation, &scope)))
+    }
+
+    /// Replace the contract code (Admin only)
+    /// Storage is kept; call `migrate` afterwards
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        let admin = Self::get_admin(env.clone())?;
+        upgrade_wasm(&env, &admin, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Stamp storage with schema version 1 (Admin only)
+    /// Nothing is moved: version 1 only adds operator delegations
+    pub fn migrate(env: Env) -> Result<u32, ContractError> {
+        let admin = Self::get_admin(env.clone())?;
+        let Some(from) = pending_migration(&env, &admin, SCHEMA_VERSION) else {
+            panic_with_error!(&env, ContractError::AlreadyMigrated);
+        };
+
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    /// Get the storage schema version of this deployment
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
+
     pub fn is_initialized(env: Env) -> bool {
         env.storage().instance().has(&DataKey::Initialized)
     }
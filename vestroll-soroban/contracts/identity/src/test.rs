@@ -0,0 +1,128 @@
+use crate::{ContractError, IdentityManagementContract, IdentityManagementContractClient, OperatorScope};
+use soroban_sdk::{
+    testutils::{Address as _, Ledger as _},
+    vec, Address, Env,
+};
+
+fn setup() -> (Env, IdentityManagementContractClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(IdentityManagementContract, ());
+    let client = IdentityManagementContractClient::new(&env, &contract_id);
+    client.initialize(&Address::generate(&env));
+    (env, client)
+}
+
+#[test]
+fn test_grant_and_revoke_operator() {
+    let (env, client) = setup();
+    let employer = Address::generate(&env);
+    let other_employer = Address::generate(&env);
+    let clerk = Address::generate(&env);
+    env.ledger().with_mut(|li| li.timestamp = 500);
+
+    let scopes = vec![&env, OperatorScope::ProcessPayments, OperatorScope::CreateContracts];
+    client.grant_operator(&employer, &clerk, &scopes, &Some(1_000), &Some(10_000));
+
+    let delegation = client.get_delegation(&employer, &clerk).unwrap();
+    assert_eq!(delegation.scopes, scopes);
+    assert_eq!(delegation.cap, Some(1_000));
+    assert_eq!(delegation.expires_at, Some(10_000));
+    assert_eq!(delegation.granted_at, 500);
+
+    assert!(client.is_operator(&employer, &clerk, &OperatorScope::ProcessPayments));
+    assert!(client.is_operator(&employer, &clerk, &OperatorScope::CreateContracts));
+    assert!(!client.is_operator(&employer, &clerk, &OperatorScope::CompleteMilestones));
+    // A delegation only covers the principal that granted it.
+    assert!(!client.is_operator(&other_employer, &clerk, &OperatorScope::ProcessPayments));
+    assert_eq!(client.get_delegation(&other_employer, &clerk), None);
+
+    client.revoke_operator(&employer, &clerk);
+    assert_eq!(client.get_delegation(&employer, &clerk), None);
+    assert!(!client.is_operator(&employer, &clerk, &OperatorScope::ProcessPayments));
+    assert!(matches!(
+        client.try_revoke_operator(&employer, &clerk),
+        Err(Ok(ContractError::DelegationNotFound))
+    ));
+}
+
+#[test]
+fn test_regrant_replaces_delegation() {
+    let (env, client) = setup();
+    let employer = Address::generate(&env);
+    let clerk = Address::generate(&env);
+
+    client.grant_operator(&employer, &clerk, &vec![&env, OperatorScope::ProcessPayments], &Some(1_000), &None);
+    env.ledger().with_mut(|li| li.timestamp = 700);
+    client.grant_operator(&employer, &clerk, &vec![&env, OperatorScope::CompleteMilestones], &None, &None);
+
+    // Scopes, cap and the grant time all come from the latest grant.
+    let delegation = client.get_delegation(&employer, &clerk).unwrap();
+    assert_eq!(delegation.scopes, vec![&env, OperatorScope::CompleteMilestones]);
+    assert_eq!(delegation.cap, None);
+    assert_eq!(delegation.granted_at, 700);
+    assert!(!client.is_operator(&employer, &clerk, &OperatorScope::ProcessPayments));
+    assert!(client.is_operator(&employer, &clerk, &OperatorScope::CompleteMilestones));
+}
+
+#[test]
+fn test_operator_delegation_expires() {
+    let (env, client) = setup();
+    let employer = Address::generate(&env);
+    let clerk = Address::generate(&env);
+
+    client.grant_operator(&employer, &clerk, &vec![&env, OperatorScope::ProcessPayments], &None, &Some(1_000));
+
+    env.ledger().with_mut(|li| li.timestamp = 999);
+    assert!(client.is_operator(&employer, &clerk, &OperatorScope::ProcessPayments));
+
+    // Expired delegations stop counting but stay readable.
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    assert!(!client.is_operator(&employer, &clerk, &OperatorScope::ProcessPayments));
+    assert_eq!(client.get_delegation(&employer, &clerk).unwrap().expires_at, Some(1_000));
+}
+
+#[test]
+fn test_grant_operator_rejects_invalid_delegations() {
+    let (env, client) = setup();
+    let employer = Address::generate(&env);
+    let clerk = Address::generate(&env);
+    let scopes = vec![&env, OperatorScope::ProcessPayments];
+    env.ledger().with_mut(|li| li.timestamp = 500);
+
+    assert!(matches!(
+        client.try_grant_operator(&employer, &clerk, &vec![&env], &None, &None),
+        Err(Ok(ContractError::InvalidDelegation))
+    ));
+    assert!(matches!(
+        client.try_grant_operator(&employer, &clerk, &scopes, &Some(0), &None),
+        Err(Ok(ContractError::InvalidDelegation))
+    ));
+    assert!(matches!(
+        client.try_grant_operator(&employer, &clerk, &scopes, &None, &Some(500)),
+        Err(Ok(ContractError::InvalidDelegation))
+    ));
+    assert!(matches!(
+        client.try_grant_operator(&employer, &employer, &scopes, &None, &None),
+        Err(Ok(ContractError::SelfDelegation))
+    ));
+    assert_eq!(client.get_delegation(&employer, &clerk), None);
+}
+
+#[test]
+fn test_grant_operator_requires_principal_auth() {
+    let env = Env::default();
+    let contract_id = env.register(IdentityManagementContract, ());
+    let client = IdentityManagementContractClient::new(&env, &contract_id);
+    let admin = Address::generate(&env);
+    env.mock_all_auths();
+    client.initialize(&admin);
+    env.set_auths(&[]);
+
+    let employer = Address::generate(&env);
+    let clerk = Address::generate(&env);
+    let scopes = vec![&env, OperatorScope::ProcessPayments];
+    assert!(client.try_grant_operator(&employer, &clerk, &scopes, &None, &None).is_err());
+    assert_eq!(client.get_delegation(&employer, &clerk), None);
+}
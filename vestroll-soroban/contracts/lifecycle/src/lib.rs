@@ -1,9 +1,15 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 mod test_lifecycle;
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Vec, String, symbol_short};
+use soroban_sdk::{contract, contractimpl, contracttype, Address, BytesN, Env, IntoVal, Map, Symbol, Val, Vec, String, symbol_short};
 
-use vestroll_common::{ContractType, ContractMetadata, LifecycleError, ContractStatus};
+use vestroll_common::{
+    complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm, ContractType, ContractMetadata,
+    LifecycleError, ContractStatus, Payment, ReservedPayout, INSTANCE_BUMP_AMOUNT, INSTANCE_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT,
+    RECORD_BUMP_THRESHOLD,
+};
+use vestroll_identity::{check_operator, Delegation, IdentityManagementContractClient, OperatorScope};
 use vestroll_profile::{ProfileContractClient};
 use vestroll_vault::{VaultContractClient};
 
@@ -22,6 +28,38 @@ pub enum DataKey {
     VaultAddress,
     ProfileAddress,
     ProtocolAsset,
+    Schedule(u32),
+    Stream(u32),
+    PendingAmendment(u32),
+    ContractVersion(u32, u32),
+    MilestoneHistory(u32, u32),
+    IdentityAddress,
+    Dispute(u32),
+    Termination(u32),
+    Suspension(u32),
+    HourlyRate(u32),
+    Timesheet(u32, u32),
+    UsageTerms(u32),
+    UsageReport(u32, u32),
+    PaymentCount(u32),
+    Payment(u32, u32),
+    Payees(u32),
+    Deductions(u32),
+    ClaimCount(u32),
+    Claim(u32, u32),
+    ClaimApprover(Address, Address),
+    AdvancePolicy(u32),
+    AdvanceCount(u32),
+    Advance(u32, u32),
+    OperatorUsage(Address, Address),
+    NextPayrollRunId,
+    KeeperTerms(u32),
+    PayrollRun(u32),
+    MigrationCursor,
+    PaidMilestones(u32),
+    OpenTimesheets(u32),
+    OpenUsageReports(u32),
+    PayrollApprovers(Address),
 }
 
 #[contracttype]
@@ -38,6 +76,29 @@ pub struct Contract {
     pub metadata: ContractMetadata,
     pub created_at: u64,
     pub milestones: Option<Vec<Milestone>>,
+    pub offer_expires_at: u64,
+    pub accepted_at: Option<u64>,
+    pub version: u32,
+    pub start_date: u64,
+    pub end_date: Option<u64>,
+}
+
+/// A contract as deployments before schema version 1 stored it, in instance
+/// storage. `migrate_storage` converts these records into `Contract`s.
+#[contracttype]
+#[derive(Clone)]
+pub struct LegacyContract {
+    pub id: u32,
+    pub employer: Address,
+    pub employee: Address,
+    pub contract_type: ContractType,
+    pub status: ContractStatus,
+    pub total_amount: i128,
+    pub paid_amount: i128,
+    pub asset: Address,
+    pub metadata: ContractMetadata,
+    pub created_at: u64,
+    pub milestones: Option<Vec<Milestone>>,
 }
 
 #[contracttype]
@@ -50,6 +111,464 @@ pub struct Milestone {
     pub completed_at: Option<u64>,
 }
 
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReviewAction {
+    Submitted,
+    Approved,
+    Rejected,
+}
+
+/// One entry in a milestone's submission/review trail. `note` holds the
+/// deliverable hash or URI for submissions and the reason for rejections.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MilestoneReview {
+    pub action: ReviewAction,
+    pub actor: Address,
+    pub note: String,
+    pub timestamp: u64,
+}
+
+/// An open disagreement on a contract or one of its milestones. `amount` is
+/// what the arbiter splits between employee payout and employer refund.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dispute {
+    pub contract_id: u32,
+    pub raised_by: Address,
+    pub milestone_id: Option<u32>,
+    pub amount: i128,
+    pub reason: String,
+    pub raised_at: u64,
+}
+
+/// Why and since when a contract is on hold.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suspension {
+    pub suspended_by: Address,
+    pub reason: String,
+    pub suspended_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimesheetStatus {
+    Submitted,
+    Approved,
+    Rejected,
+    Paid,
+}
+
+/// Hours a worker logged for one period of an hourly PayAsYouGo contract.
+/// `reference` is the hash of the off-chain timesheet backing the claim.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Timesheet {
+    pub period: u32,
+    pub hours: u32,
+    pub reference: BytesN<32>,
+    pub status: TimesheetStatus,
+    pub submitted_at: u64,
+    pub reviewed_at: Option<u64>,
+}
+
+/// Metered billing terms of a PayAsYouGo contract. `reporter` is the system
+/// trusted by both parties to report usage; reports become payable
+/// `challenge_window` seconds after they are filed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageTerms {
+    pub reporter: Address,
+    pub unit_price: i128,
+    pub challenge_window: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UsageStatus {
+    Pending,
+    Challenged,
+    Paid,
+}
+
+/// Units consumed in one reporting period, signed by the contract's reporter.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageReport {
+    pub period: u32,
+    pub units: u32,
+    pub reference: BytesN<32>,
+    pub status: UsageStatus,
+    pub reported_at: u64,
+    pub challenged_by: Option<Address>,
+}
+
+/// How a team contract's payee shares in each payout. Fixed amounts are paid
+/// first; percentages (in basis points, summing to 10 000) split the rest.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SplitRule {
+    Percent(u32),
+    Fixed(i128),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Payee {
+    pub address: Address,
+    pub rule: SplitRule,
+}
+
+/// An amount withheld from every payout, such as income tax, a pension
+/// contribution or union dues, and remitted to `recipient`. Percentages are
+/// taken from the gross payout.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Deduction {
+    pub name: String,
+    pub recipient: Address,
+    pub rule: SplitRule,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ClaimStatus {
+    Submitted,
+    Approved,
+    Rejected,
+    Paid,
+}
+
+/// One step in an expense claim's life: who moved it to `status`, and when.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClaimStatusChange {
+    pub status: ClaimStatus,
+    pub actor: Address,
+    pub timestamp: u64,
+}
+
+/// A worker's request to be reimbursed for an expense such as travel or
+/// equipment. `receipt` is the hash of the off-chain receipt. Reimbursements
+/// are paid on top of the contract's `total_amount`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpenseClaim {
+    pub id: u32,
+    pub amount: i128,
+    pub category: Symbol,
+    pub receipt: BytesN<32>,
+    pub status: ClaimStatus,
+    pub history: Vec<ClaimStatusChange>,
+}
+
+/// How much of its remaining value a contract may pay out early.
+/// `max_share_bps` caps an advance as a share of `total_amount - paid_amount`;
+/// requests up to `auto_approve_limit` are paid without the employer's review.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdvancePolicy {
+    pub max_share_bps: u32,
+    pub auto_approve_limit: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AdvanceStatus {
+    Requested,
+    Outstanding,
+    Repaid,
+    Rejected,
+    WrittenOff,
+}
+
+/// An earned-wage advance. `outstanding` is what later payouts still have
+/// to recover.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Advance {
+    pub id: u32,
+    pub amount: i128,
+    pub outstanding: i128,
+    pub status: AdvanceStatus,
+    pub requested_at: u64,
+}
+
+/// What a payroll run pays a contract: a set amount, or every period of its
+/// FixedRate schedule that has fallen due.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PayrollAmount {
+    Amount(i128),
+    DuePeriods,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollItem {
+    pub contract_id: u32,
+    pub amount: PayrollAmount,
+}
+
+/// `AllOrNothing` fails the whole run on the first bad item; `BestEffort`
+/// skips it and pays the rest.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PayrollMode {
+    AllOrNothing,
+    BestEffort,
+}
+
+/// How one item of a payroll run went. `skipped` holds the `LifecycleError`
+/// code of an item that was not paid.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollOutcome {
+    pub contract_id: u32,
+    pub paid: i128,
+    pub skipped: Option<u32>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollReport {
+    pub run_id: u32,
+    pub outcomes: Vec<PayrollOutcome>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PayrollRunStatus {
+    Draft,
+    Approved,
+    Executed,
+    Cancelled,
+}
+
+/// One payment of a drafted payroll run, as it stood when the run was drafted.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollLine {
+    pub contract_id: u32,
+    pub payee: Address,
+    pub asset: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssetTotal {
+    pub asset: Address,
+    pub amount: i128,
+}
+
+/// A payroll run awaiting sign-off. `lines` and `totals` are frozen when the
+/// run is drafted or edited; it executes once `threshold` of `approvers` have
+/// approved that exact snapshot.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollRun {
+    pub id: u32,
+    pub employer: Address,
+    pub preparer: Address,
+    pub lines: Vec<PayrollLine>,
+    pub totals: Vec<AssetTotal>,
+    pub approvers: Vec<Address>,
+    pub threshold: u32,
+    pub approvals: Vec<Address>,
+    pub status: PayrollRunStatus,
+    pub created_at: u64,
+}
+
+/// Who signs off on an employer's payroll runs: `threshold` of `approvers`.
+/// Set by the employer; each run keeps the set it was drafted under.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollApprovers {
+    pub approvers: Vec<Address>,
+    pub threshold: u32,
+}
+
+/// Standing authorization for keepers to run a contract's scheduled
+/// payments. `reserved` is what is left of the reward pool the employer
+/// funded up front.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeeperTerms {
+    pub reward: i128,
+    pub reserved: i128,
+}
+
+/// A payout worked out but not yet made: the transfers out of the contract's
+/// reservation and the figures for its payslip.
+struct Payslip {
+    payments: Vec<Payment>,
+    remittances: Vec<(String, Address, i128)>,
+    recovered: i128,
+    deducted: i128,
+    net: i128,
+    fee: i128,
+}
+
+/// Work a closing contract has approved but not paid for yet.
+struct UnpaidWork {
+    milestones: Vec<u32>,
+    timesheets: Vec<u32>,
+    usage_reports: Vec<u32>,
+    amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PaymentKind {
+    Fixed,
+    Scheduled,
+    Stream,
+    Milestone,
+    PayAsYouGo,
+    Timesheet,
+    Usage,
+    Settlement,
+    DisputeResolution,
+    Advance,
+}
+
+/// One payout to the employee, kept on-chain so payments can be reconciled
+/// from contract state. `seq` numbers a contract's payments from 0; `amount`
+/// is the gross, of which `deducted` was withheld, `fee` went to the vault
+/// as its protocol fee and `net` was paid out.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentRecord {
+    pub seq: u32,
+    pub amount: i128,
+    pub deducted: i128,
+    pub net: i128,
+    pub fee: i128,
+    pub asset: Address,
+    pub timestamp: u64,
+    pub kind: PaymentKind,
+    pub milestone_id: Option<u32>,
+    pub memo: Option<String>,
+}
+
+/// Narrows a contract query. A contract matches when each list is empty or
+/// contains the contract's value.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContractFilter {
+    pub statuses: Vec<ContractStatus>,
+    pub contract_types: Vec<ContractType>,
+    pub assets: Vec<Address>,
+}
+
+/// One page of a contract query. Pass `next_cursor` back to continue;
+/// `None` means the list has been read to the end.
+#[contracttype]
+#[derive(Clone)]
+pub struct ContractPage {
+    pub contracts: Vec<Contract>,
+    pub next_cursor: Option<u32>,
+}
+
+/// What the employee is owed if the employer ends the contract early.
+/// `notice_period` is in seconds and is paid in lieu on termination.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TerminationTerms {
+    pub notice_period: u64,
+    pub severance: i128,
+}
+
+/// Breakdown of a final termination payout.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Settlement {
+    pub earned: i128,
+    pub severance: i128,
+    pub employer_refund: i128,
+}
+
+/// How often a scheduled FixedRate contract pays out.
+/// Months are treated as fixed 30-day periods.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PaymentCadence {
+    Weekly,
+    Biweekly,
+    Monthly,
+}
+
+/// Recurring salary schedule for a FixedRate contract.
+/// Each period is paid in arrears once its end has passed; `periods_paid`
+/// tracks how many periods have already been settled.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentSchedule {
+    pub cadence: PaymentCadence,
+    pub amount_per_period: i128,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub periods_paid: u32,
+}
+
+/// Accrual window of a Streaming contract. `accrued_before` had vested by
+/// `start_time`; the rest of the contract's `total_amount` vests linearly
+/// over `[start_time, end_time]`. An amendment to the term or the total
+/// restarts the window at the time it is approved.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stream {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub accrued_before: i128,
+}
+
+/// Set of changes an amendment applies to a contract. Fields left as `None`
+/// are kept unchanged. A new `end_date` is how an Active contract is renewed;
+/// it moves the end of the contract's payment schedule or stream with it.
+/// Expired contracts are settled and cannot be renewed; the parties agree a
+/// new contract instead.
+#[contracttype]
+#[derive(Clone)]
+pub struct AmendmentChanges {
+    pub total_amount: Option<i128>,
+    pub asset: Option<Address>,
+    pub add_milestones: Option<Vec<Milestone>>,
+    pub end_date: Option<u64>,
+}
+
+/// A change proposed by one party that waits for the counterparty's approval.
+/// It can only be approved while `base_version` is still the contract's
+/// current version.
+#[contracttype]
+#[derive(Clone)]
+pub struct Amendment {
+    pub contract_id: u32,
+    pub proposer: Address,
+    pub base_version: u32,
+    pub changes: AmendmentChanges,
+    pub proposed_at: u64,
+}
+
+const DAY_IN_SECONDS: u64 = 86_400;
+/// Storage schema version this code expects. Version 1 keeps contracts in
+/// per-record persistent storage.
+const SCHEMA_VERSION: u32 = 1;
+/// Largest page the contract queries return.
+const MAX_PAGE_SIZE: u32 = 50;
+/// Most ids a single query inspects, so sparse filters stay within read limits.
+const MAX_PAGE_SCAN: u32 = 200;
+/// Most payees a team contract can split between.
+const MAX_PAYEES: u32 = 20;
+/// Most deductions a contract can withhold from each payout.
+const MAX_DEDUCTIONS: u32 = 10;
+const BASIS_POINTS: u32 = 10_000;
+/// Most contracts a single payroll run can pay.
+const MAX_PAYROLL_ITEMS: u32 = 100;
+
 #[contractimpl]
 impl LifecycleContract {
     pub fn initialize(
@@ -71,289 +590,2944 @@ impl LifecycleContract {
         env.storage().instance().set(&DataKey::ProtocolAsset, &protocol_asset);
         env.storage().instance().set(&DataKey::NextContractId, &1u32);
         env.storage().instance().set(&DataKey::Initialized, &true);
+        set_schema_version(&env, SCHEMA_VERSION);
 
         Ok(())
     }
 
+    /// Creates a contract offer for `metadata.employer`. `caller` is the
+    /// employer, or an operator holding a CreateContracts delegation granted
+    /// by that same employer; a delegation from anyone else is rejected.
+    /// The first argument used to be the employer itself: callers that pass
+    /// the employer keep working, and the offer always belongs to
+    /// `metadata.employer` whoever signs.
     pub fn create_contract(
         env: Env,
-        employer: Address,
+        caller: Address,
         employee: Address,
         contract_type: ContractType,
         total_amount: i128,
         asset: Address,
         metadata: ContractMetadata,
         milestones: Option<Vec<Milestone>>,
+        schedule: Option<PaymentSchedule>,
+        offer_expires_at: u64,
     ) -> Result<u32, LifecycleError> {
-        employer.require_auth();
+        caller.require_auth();
         Self::ensure_initialized(&env)?;
+        let employer = metadata.employer.clone();
+        let delegation = Self::authorize_employer(&env, &employer, &caller, OperatorScope::CreateContracts)?;
+        Self::charge_operator(&env, &employer, &caller, delegation, total_amount)?;
+        if offer_expires_at <= env.ledger().timestamp() { return Err(LifecycleError::OfferExpired); }
         
         match contract_type {
             ContractType::Milestone => {
-                if milestones.is_none() { return Err(LifecycleError::InvalidMilestoneData); }
+                let Some(milestones) = milestones.as_ref() else { return Err(LifecycleError::InvalidMilestoneData); };
+                Self::validate_milestones(milestones, total_amount)?;
             }
+            ContractType::Streaming => return Err(LifecycleError::InvalidContractType),
             _ => {
                 if milestones.is_some() { return Err(LifecycleError::InvalidContractType); }
             }
         }
 
+        let schedule = match schedule {
+            Some(mut schedule) => {
+                if contract_type != ContractType::FixedRate { return Err(LifecycleError::InvalidContractType); }
+                Self::validate_schedule(&schedule, total_amount)?;
+                schedule.periods_paid = 0;
+                Some(schedule)
+            }
+            None => None,
+        };
+
         Self::ensure_employee_can_receive_payment(&env, &employee, &asset)?;
 
-        let contract_id = Self::get_next_id(&env);
-        let contract = Contract {
-            id: contract_id,
-            employer: employer.clone(),
-            employee: employee.clone(),
-            contract_type: contract_type.clone(),
-            status: ContractStatus::Active,
-            total_amount,
-            paid_amount: 0,
-            asset: asset.clone(),
-            metadata: metadata.clone(),
-            created_at: env.ledger().timestamp(),
-            milestones,
-        };
+        let contract_id = Self::store_new_contract(
+            &env, employer, employee, contract_type, total_amount, asset, metadata, milestones, offer_expires_at,
+            env.ledger().timestamp(), None,
+        )?;
+        if let Some(schedule) = schedule {
+            Self::persist(&env, &DataKey::Schedule(contract_id), &schedule);
+        }
 
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
-        Self::add_to_employer_list(&env, employer.clone(), contract_id);
-        Self::add_to_employee_list(&env, employee.clone(), contract_id);
-        env.storage().instance().set(&DataKey::NextContractId, &(contract_id + 1));
+        Ok(contract_id)
+    }
 
-        env.events().publish(
-            (symbol_short!("CREATE"), contract_id),
-            (employer, employee, contract_type),
-        );
+    /// Creates a Streaming contract offer whose `total_amount` accrues linearly to
+    /// the employee between `start_time` and `end_time` once accepted. As with
+    /// `create_contract`, the offer belongs to `metadata.employer`.
+    pub fn create_stream(
+        env: Env,
+        caller: Address,
+        employee: Address,
+        total_amount: i128,
+        asset: Address,
+        metadata: ContractMetadata,
+        start_time: u64,
+        end_time: u64,
+        offer_expires_at: u64,
+    ) -> Result<u32, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+        let employer = metadata.employer.clone();
+        let delegation = Self::authorize_employer(&env, &employer, &caller, OperatorScope::CreateContracts)?;
+        Self::charge_operator(&env, &employer, &caller, delegation, total_amount)?;
+        if offer_expires_at <= env.ledger().timestamp() { return Err(LifecycleError::OfferExpired); }
+
+        if total_amount <= 0 || end_time <= start_time { return Err(LifecycleError::InvalidSchedule); }
+
+        Self::ensure_employee_can_receive_payment(&env, &employee, &asset)?;
+
+        let contract_id = Self::store_new_contract(
+            &env, employer, employee, ContractType::Streaming, total_amount, asset, metadata, None, offer_expires_at,
+            // A stream's term is its accrual window.
+            start_time, Some(end_time),
+        )?;
+        Self::persist(&env, &DataKey::Stream(contract_id), &Stream { start_time, end_time, accrued_before: 0 });
 
         Ok(contract_id)
     }
 
     pub fn process_fixed_payment(
         env: Env,
-        employer: Address,
+        caller: Address,
         contract_id: u32,
         amount: i128,
+        memo: Option<String>,
     ) -> Result<(), LifecycleError> {
-        employer.require_auth();
+        caller.require_auth();
         Self::ensure_initialized(&env)?;
 
         let mut contract = Self::get_contract_internal(&env, contract_id)?;
-        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        let delegation = Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::ProcessPayments)?;
         if contract.contract_type != ContractType::FixedRate { return Err(LifecycleError::InvalidContractType); }
         if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
 
         let remaining = contract.total_amount - contract.paid_amount;
         if amount > remaining { return Err(LifecycleError::InsufficientContractFunds); }
 
-        Self::ensure_employee_can_receive_payment(&env, &contract.employee, &contract.asset)?;
-        Self::process_vault_payment(&env, &contract.employee, amount, &contract.asset)?;
+        Self::charge_operator(&env, &contract.employer, &caller, delegation, amount)?;
+        Self::pay_employee(&env, &contract, amount, PaymentKind::Fixed, None, memo)?;
 
         contract.paid_amount += amount;
         if contract.paid_amount >= contract.total_amount {
             contract.status = ContractStatus::Completed;
+            Self::release_keeper_pool(&env, contract_id)?;
         }
 
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
         env.events().publish(
             (symbol_short!("PAYMENT"), contract_id),
-            (employer, contract.employee, amount),
+            (caller, contract.employee, amount),
         );
 
         Ok(())
     }
 
-    pub fn complete_milestone(
+    /// Employee accepts a Draft offer, activating the contract. Payments are only
+    /// possible after this call, so it doubles as the worker's signed consent.
+    pub fn accept_contract(
         env: Env,
-        employer: Address,
+        employee: Address,
         contract_id: u32,
-        milestone_id: u32,
     ) -> Result<(), LifecycleError> {
-        employer.require_auth();
+        employee.require_auth();
         Self::ensure_initialized(&env)?;
 
         let mut contract = Self::get_contract_internal(&env, contract_id)?;
-        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
-        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
-        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
 
-        let mut milestones = contract.milestones.ok_or(LifecycleError::InvalidMilestoneData)?;
-        let mut found = false;
+        let now = env.ledger().timestamp();
+        if now > contract.offer_expires_at { return Err(LifecycleError::OfferExpired); }
 
-        for i in 0..milestones.len() {
-            let mut milestone = milestones.get(i).unwrap();
-            if milestone.id == milestone_id {
-                if milestone.completed { return Err(LifecycleError::MilestoneAlreadyCompleted); }
-                milestone.completed = true;
-                milestone.completed_at = Some(env.ledger().timestamp());
-                milestones.set(i, milestone);
-                found = true;
-                break;
-            }
-        }
+        contract.status = ContractStatus::Active;
+        contract.accepted_at = Some(now);
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
 
-        if !found { return Err(LifecycleError::MilestoneNotFound); }
+        env.events().publish(
+            (symbol_short!("ACCEPT"), contract_id),
+            (contract.employer, employee, now),
+        );
 
-        contract.milestones = Some(milestones);
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
         Ok(())
     }
 
-    pub fn process_milestone_payment(
+    /// Employee turns down a Draft offer. The contract is closed as Cancelled.
+    pub fn reject_contract(
         env: Env,
-        employer: Address,
+        employee: Address,
         contract_id: u32,
-        milestone_id: u32,
     ) -> Result<(), LifecycleError> {
-        employer.require_auth();
+        employee.require_auth();
         Self::ensure_initialized(&env)?;
 
         let mut contract = Self::get_contract_internal(&env, contract_id)?;
-        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
-        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
-        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
-
-        let milestones = contract.milestones.as_ref().ok_or(LifecycleError::InvalidMilestoneData)?;
-        let mut milestone_amount = 0;
-        let mut milestone_found = false;
-        let mut all_completed = true;
-
-        for i in 0..milestones.len() {
-            let milestone = milestones.get(i).unwrap();
-            if milestone.id == milestone_id {
-                if !milestone.completed { return Err(LifecycleError::MilestoneNotCompleted); }
-                milestone_amount = milestone.amount;
-                milestone_found = true;
-            }
-            if !milestone.completed { all_completed = false; }
-        }
-
-        if !milestone_found { return Err(LifecycleError::MilestoneNotFound); }
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
 
-        Self::ensure_employee_can_receive_payment(&env, &contract.employee, &contract.asset)?;
-        Self::process_vault_payment(&env, &contract.employee, milestone_amount, &contract.asset)?;
-
-        contract.paid_amount += milestone_amount;
-        if all_completed && contract.paid_amount >= contract.total_amount {
-            contract.status = ContractStatus::Completed;
-        }
+        Self::release_funds(&env, contract_id, contract.total_amount)?;
+        Self::release_keeper_pool(&env, contract_id)?;
+        contract.status = ContractStatus::Cancelled;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
 
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
         env.events().publish(
-            (symbol_short!("MILESTONE"), contract_id, milestone_id),
-            (employer, contract.employee, milestone_amount),
+            (symbol_short!("REJECT"), contract_id),
+            (contract.employer, employee),
         );
 
         Ok(())
     }
 
-    pub fn process_payg_payment(
+    /// Pays every schedule period that has ended since the last scheduled payout.
+    /// Returns the number of periods paid; fails with `NoPaymentDue` when nothing
+    /// new has come due, so a period can never be paid twice.
+    pub fn process_scheduled_payment(
         env: Env,
-        employer: Address,
+        caller: Address,
+        contract_id: u32,
+    ) -> Result<u32, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        let delegation = Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::ProcessPayments)?;
+
+        let (periods, amount) = Self::pay_scheduled(&env, &mut contract)?;
+        Self::charge_operator(&env, &contract.employer, &caller, delegation, amount)?;
+        Ok(periods)
+    }
+
+    /// Lets the employee pull everything that has accrued on a stream but has not
+    /// been paid yet. Returns the amount withdrawn.
+    pub fn withdraw_streamed(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+    ) -> Result<i128, LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.contract_type != ContractType::Streaming { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let amount = Self::streamed_balance(&env, &contract)?;
+        if amount <= 0 { return Err(LifecycleError::NoPaymentDue); }
+
+        Self::pay_employee(&env, &contract, amount, PaymentKind::Stream, None, None)?;
+
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+        }
+
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        env.events().publish(
+            (symbol_short!("STREAM"), contract_id),
+            (employee, amount),
+        );
+
+        Ok(amount)
+    }
+
+    pub fn complete_milestone(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        milestone_id: u32,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::CompleteMilestones)?;
+        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        Self::mark_milestone_completed(&env, &mut contract, milestone_id)?;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        Ok(())
+    }
+
+    /// Worker declares a milestone delivered. `deliverable` carries a content
+    /// hash or URI the employer reviews before approving.
+    pub fn submit_milestone(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+        milestone_id: u32,
+        deliverable: String,
+    ) -> Result<(), LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let milestone = Self::find_milestone(&contract, milestone_id)?;
+        if milestone.completed { return Err(LifecycleError::MilestoneAlreadyCompleted); }
+        if Self::has_pending_submission(&env, contract_id, milestone_id) {
+            return Err(LifecycleError::MilestoneAlreadySubmitted);
+        }
+
+        Self::record_milestone_review(&env, contract_id, milestone_id, ReviewAction::Submitted, &employee, deliverable.clone());
+
+        env.events().publish(
+            (symbol_short!("SUBMITTED"), contract_id, milestone_id),
+            (employee, deliverable),
+        );
+
+        Ok(())
+    }
+
+    /// Employer accepts the latest submission, marking the milestone completed
+    /// so it can be paid through `process_milestone_payment`.
+    pub fn approve_milestone(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        milestone_id: u32,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::CompleteMilestones)?;
+        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if !Self::has_pending_submission(&env, contract_id, milestone_id) {
+            return Err(LifecycleError::MilestoneNotSubmitted);
+        }
+
+        Self::mark_milestone_completed(&env, &mut contract, milestone_id)?;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        Self::record_milestone_review(&env, contract_id, milestone_id, ReviewAction::Approved, &caller, String::from_str(&env, ""));
+
+        env.events().publish((symbol_short!("APPROVED"), contract_id, milestone_id), caller);
+        Ok(())
+    }
+
+    /// Employer sends the latest submission back with a reason. The worker
+    /// can submit again afterwards.
+    pub fn reject_milestone(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        milestone_id: u32,
+        reason: String,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::CompleteMilestones)?;
+        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if !Self::has_pending_submission(&env, contract_id, milestone_id) {
+            return Err(LifecycleError::MilestoneNotSubmitted);
+        }
+
+        Self::record_milestone_review(&env, contract_id, milestone_id, ReviewAction::Rejected, &caller, reason.clone());
+
+        env.events().publish(
+            (symbol_short!("REJECTED"), contract_id, milestone_id),
+            (caller, reason),
+        );
+
+        Ok(())
+    }
+
+    /// Ids of the milestones that have been paid, or settled by an arbiter.
+    pub fn get_paid_milestones(env: Env, contract_id: u32) -> Vec<u32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PaidMilestones(contract_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Full submission and review trail of a milestone, oldest first.
+    pub fn get_milestone_history(env: Env, contract_id: u32, milestone_id: u32) -> Vec<MilestoneReview> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MilestoneHistory(contract_id, milestone_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn process_milestone_payment(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        milestone_id: u32,
+        memo: Option<String>,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        let delegation = Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::ProcessPayments)?;
+        if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let milestones = contract.milestones.as_ref().ok_or(LifecycleError::InvalidMilestoneData)?;
+        let mut milestone_amount = 0;
+        let mut milestone_found = false;
+        let mut all_completed = true;
+
+        for i in 0..milestones.len() {
+            let milestone = milestones.get(i).unwrap();
+            if milestone.id == milestone_id {
+                if !milestone.completed { return Err(LifecycleError::MilestoneNotCompleted); }
+                if Self::is_milestone_paid(&env, contract_id, milestone_id) { return Err(LifecycleError::NoPaymentDue); }
+                milestone_amount = milestone.amount;
+                milestone_found = true;
+            }
+            if !milestone.completed { all_completed = false; }
+        }
+
+        if !milestone_found { return Err(LifecycleError::MilestoneNotFound); }
+
+        Self::charge_operator(&env, &contract.employer, &caller, delegation, milestone_amount)?;
+        Self::pay_employee(&env, &contract, milestone_amount, PaymentKind::Milestone, Some(milestone_id), memo)?;
+        Self::mark_milestone_paid(&env, contract_id, milestone_id);
+
+        contract.paid_amount += milestone_amount;
+        if all_completed && contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+        }
+
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        env.events().publish(
+            (symbol_short!("MILESTONE"), contract_id, milestone_id),
+            (caller, contract.employee, milestone_amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn process_payg_payment(
+        env: Env,
+        caller: Address,
         contract_id: u32,
         amount: i128,
+        memo: Option<String>,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        let delegation = Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::ProcessPayments)?;
+        if contract.contract_type != ContractType::PayAsYouGo { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        // Hourly and metered contracts are only paid through timesheets or usage reports.
+        if Self::is_metered(&env, contract_id) { return Err(LifecycleError::InvalidContractType); }
+
+        let remaining = contract.total_amount - contract.paid_amount;
+        if amount > remaining { return Err(LifecycleError::InsufficientContractFunds); }
+
+        Self::charge_operator(&env, &contract.employer, &caller, delegation, amount)?;
+        Self::pay_employee(&env, &contract, amount, PaymentKind::PayAsYouGo, None, memo)?;
+
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+        }
+
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        env.events().publish(
+            (symbol_short!("PAYG"), contract_id),
+            (caller, contract.employee, amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn cancel_contract(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != caller {
+            let admin = Self::get_admin(&env)?;
+            if caller != admin { return Err(LifecycleError::NotAuthorized); }
+        }
+
+        match contract.status {
+            // Employers may withdraw an offer that has not been accepted yet.
+            ContractStatus::Draft => {
+                Self::release_funds(&env, contract_id, contract.total_amount - contract.paid_amount)?;
+                Self::release_keeper_pool(&env, contract_id)?;
+            }
+            ContractStatus::Active | ContractStatus::OnHold => {
+                Self::lift_suspension(&env, &mut contract)?;
+                let terms = Self::get_termination_terms(env.clone(), contract_id);
+                let effective_at = env.ledger().timestamp() + terms.notice_period;
+                Self::settle_termination(&env, &mut contract, effective_at, terms.severance)?;
+            }
+            _ => return Err(LifecycleError::ContractNotActive),
+        }
+
+        contract.status = ContractStatus::Cancelled;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+
+        env.events().publish((symbol_short!("CANCEL"), contract_id), caller);
+        Ok(())
+    }
+
+    /// Employee-initiated termination. Settles what has accrued up to now;
+    /// notice pay and severance only apply when the employer terminates.
+    pub fn resign(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+    ) -> Result<(), LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active && contract.status != ContractStatus::OnHold {
+            return Err(LifecycleError::ContractNotActive);
+        }
+
+        Self::lift_suspension(&env, &mut contract)?;
+        Self::settle_termination(&env, &mut contract, env.ledger().timestamp(), 0)?;
+        contract.status = ContractStatus::Cancelled;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+
+        env.events().publish((symbol_short!("RESIGN"), contract_id), employee);
+        Ok(())
+    }
+
+    /// Sets the term of a Draft contract. Without an `end_date` the contract
+    /// runs until it is completed or cancelled. A payment schedule or stream
+    /// that would run past the new end date is cut short at it.
+    pub fn set_contract_term(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        start_date: u64,
+        end_date: Option<u64>,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+        if let Some(end_date) = end_date {
+            if end_date <= start_date || end_date <= env.ledger().timestamp() {
+                return Err(LifecycleError::InvalidSchedule);
+            }
+        }
+
+        let mut schedule: Option<PaymentSchedule> = env.storage().persistent().get(&DataKey::Schedule(contract_id));
+        let mut stream: Option<Stream> = env.storage().persistent().get(&DataKey::Stream(contract_id));
+        if let Some(end_date) = end_date {
+            if let Some(schedule) = schedule.as_mut().filter(|schedule| schedule.end_time > end_date) {
+                schedule.end_time = end_date;
+                Self::validate_schedule(schedule, contract.total_amount)?;
+            }
+            if let Some(stream) = stream.as_mut().filter(|stream| stream.end_time > end_date) {
+                if end_date <= stream.start_time { return Err(LifecycleError::InvalidSchedule); }
+                stream.end_time = end_date;
+            }
+        }
+
+        contract.start_date = start_date;
+        contract.end_date = end_date;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        if let Some(schedule) = schedule { Self::persist(&env, &DataKey::Schedule(contract_id), &schedule); }
+        if let Some(stream) = stream { Self::persist(&env, &DataKey::Stream(contract_id), &stream); }
+        Ok(())
+    }
+
+    /// Moves an Active contract past its end date, or a Draft offer past its
+    /// deadline, into `Expired`. Anyone may call this. Pay still accrued on
+    /// time-based contracts and approved work that was never paid are paid
+    /// out, and the unspent reservation is released.
+    pub fn expire_contract(env: Env, contract_id: u32) -> Result<(), LifecycleError> {
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if !Self::is_expired(&env, &contract) { return Err(LifecycleError::ContractNotExpired); }
+
+        Self::expire(&env, &mut contract)
+    }
+
+    /// Keeper-friendly batch form of `expire_contract`. Contracts that are not
+    /// due for expiry are skipped, as are contracts whose settlement fails, so
+    /// one unpayable worker does not hold up the rest. Returns the ids of the
+    /// contracts that were expired.
+    pub fn sweep_expired(env: Env, contract_ids: Vec<u32>) -> Result<Vec<u32>, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+
+        let mut expired = Vec::new(&env);
+        for contract_id in contract_ids.iter() {
+            let Ok(mut contract) = Self::get_contract_internal(&env, contract_id) else { continue; };
+            if !Self::is_expired(&env, &contract) { continue; }
+
+            match Self::expire(&env, &mut contract) {
+                Ok(()) => expired.push_back(contract_id),
+                Err(error) => env.events().publish((symbol_short!("EXP_FAIL"), contract_id), error),
+            }
+        }
+
+        Ok(expired)
+    }
+
+    /// Sets the notice period and severance that apply if the employer ends the
+    /// contract. Only possible on a Draft, so the employee's acceptance covers them.
+    pub fn set_termination_terms(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        terms: TerminationTerms,
     ) -> Result<(), LifecycleError> {
         employer.require_auth();
         Self::ensure_initialized(&env)?;
 
-        let mut contract = Self::get_contract_internal(&env, contract_id)?;
-        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
-        if contract.contract_type != ContractType::PayAsYouGo { return Err(LifecycleError::InvalidContractType); }
-        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+        if terms.severance < 0 || terms.severance > contract.total_amount { return Err(LifecycleError::InvalidAmount); }
+
+        Self::persist(&env, &DataKey::Termination(contract_id), &terms);
+        Ok(())
+    }
+
+    /// Termination terms of a contract; contracts without explicit terms end
+    /// immediately with no severance.
+    pub fn get_termination_terms(env: Env, contract_id: u32) -> TerminationTerms {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Termination(contract_id))
+            .unwrap_or(TerminationTerms { notice_period: 0, severance: 0 })
+    }
+
+    // ====================================================================
+    // Amendments
+    // ====================================================================
+
+    /// Either party proposes a change set for an Active contract. Only one
+    /// amendment can be pending at a time.
+    pub fn propose_amendment(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        changes: AmendmentChanges,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != contract.employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let key = DataKey::PendingAmendment(contract_id);
+        if env.storage().persistent().has(&key) { return Err(LifecycleError::AmendmentPending); }
+
+        // Validate up front so the counterparty is never asked to sign an unusable change.
+        Self::apply_changes(&env, contract.clone(), &changes)?;
+
+        let amendment = Amendment {
+            contract_id,
+            proposer: caller.clone(),
+            base_version: contract.version,
+            changes,
+            proposed_at: env.ledger().timestamp(),
+        };
+        Self::persist(&env, &key, &amendment);
+
+        env.events().publish(
+            (symbol_short!("PROPOSE"), contract_id),
+            (caller, contract.version),
+        );
+
+        Ok(())
+    }
+
+    /// The counterparty approves the pending amendment. The previous terms are
+    /// archived as a version and the changes are applied in one step.
+    /// Returns the new contract version.
+    pub fn approve_amendment(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+    ) -> Result<u32, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let key = DataKey::PendingAmendment(contract_id);
+        let amendment: Amendment = env.storage().persistent().get(&key).ok_or(LifecycleError::AmendmentNotFound)?;
+
+        let counterparty = if amendment.proposer == contract.employer { &contract.employee } else { &contract.employer };
+        if caller != *counterparty { return Err(LifecycleError::NotAuthorized); }
+        // The terms were proposed against a version that has since been replaced.
+        if amendment.base_version != contract.version { return Err(LifecycleError::InvalidAmendment); }
+
+        let mut amended = Self::apply_changes(&env, contract.clone(), &amendment.changes)?;
+        amended.version = contract.version + 1;
+        Self::rebalance_reservation(&env, &contract, &amended)?;
+        Self::amend_windows(&env, &contract, &amended)?;
+
+        Self::persist(&env, &DataKey::ContractVersion(contract_id, contract.version), &contract);
+        Self::persist(&env, &DataKey::Contract(contract_id), &amended);
+        env.storage().persistent().remove(&key);
+
+        env.events().publish(
+            (symbol_short!("AMENDED"), contract_id),
+            (amendment.proposer, caller, amended.version),
+        );
+
+        Ok(amended.version)
+    }
+
+    /// Drops the pending amendment. The counterparty uses this to reject a
+    /// proposal and the proposer to withdraw it.
+    pub fn reject_amendment(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != contract.employee { return Err(LifecycleError::NotAuthorized); }
+
+        let key = DataKey::PendingAmendment(contract_id);
+        if !env.storage().persistent().has(&key) { return Err(LifecycleError::AmendmentNotFound); }
+        env.storage().persistent().remove(&key);
+
+        env.events().publish((symbol_short!("AMEND_REJ"), contract_id), caller);
+        Ok(())
+    }
+
+    pub fn get_pending_amendment(env: Env, contract_id: u32) -> Option<Amendment> {
+        env.storage().persistent().get(&DataKey::PendingAmendment(contract_id))
+    }
+
+    /// Returns the contract as it stood at `version`. The current version is
+    /// served from the live record.
+    pub fn get_contract_version(env: Env, contract_id: u32, version: u32) -> Result<Contract, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if version == contract.version { return Ok(contract); }
+
+        env.storage()
+            .persistent()
+            .get(&DataKey::ContractVersion(contract_id, version))
+            .ok_or(LifecycleError::ContractNotFound)
+    }
+
+    /// Every version of the contract, oldest first, ending with the live terms.
+    pub fn get_contract_history(env: Env, contract_id: u32) -> Result<Vec<Contract>, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+
+        let mut history = Vec::new(&env);
+        for version in 1..contract.version {
+            if let Some(previous) = env.storage().persistent().get(&DataKey::ContractVersion(contract_id, version)) {
+                history.push_back(previous);
+            }
+        }
+        history.push_back(contract);
+
+        Ok(history)
+    }
+
+    // ====================================================================
+    // Disputes
+    // ====================================================================
+
+    /// Either party freezes an Active contract pending arbitration. Without a
+    /// milestone the whole unpaid remainder is disputed, less any outstanding
+    /// advance, which the employee already holds.
+    pub fn raise_dispute(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        milestone_id: Option<u32>,
+        reason: String,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != contract.employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let remaining = contract.total_amount - contract.paid_amount - Self::get_outstanding_advance(env.clone(), contract_id);
+        let amount = match milestone_id {
+            Some(id) => {
+                if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+                if Self::is_milestone_paid(&env, contract_id, id) { return Err(LifecycleError::NoPaymentDue); }
+                Self::find_milestone(&contract, id)?.amount
+            }
+            None => remaining,
+        };
+        if amount > remaining { return Err(LifecycleError::InsufficientContractFunds); }
+
+        let dispute = Dispute {
+            contract_id,
+            raised_by: caller.clone(),
+            milestone_id,
+            amount,
+            reason,
+            raised_at: env.ledger().timestamp(),
+        };
+
+        contract.status = ContractStatus::Disputed;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        Self::persist(&env, &DataKey::Dispute(contract_id), &dispute);
+
+        env.events().publish(
+            (symbol_short!("DISPUTE"), contract_id),
+            (caller, milestone_id, amount),
+        );
+
+        Ok(())
+    }
+
+    /// The arbiter (lifecycle admin or an identity manager) pays
+    /// `employee_amount` of the disputed sum to the worker and releases the rest
+    /// back to the employer. The contract resumes, or completes when nothing is
+    /// left to pay. When the whole contract was disputed, an outstanding
+    /// advance stays with the employee and is booked as paid; a milestone award
+    /// recovers it like any other payout.
+    pub fn resolve_dispute(
+        env: Env,
+        arbiter: Address,
+        contract_id: u32,
+        employee_amount: i128,
+    ) -> Result<(), LifecycleError> {
+        arbiter.require_auth();
+        Self::ensure_initialized(&env)?;
+        if !Self::is_arbiter(&env, &arbiter)? { return Err(LifecycleError::NotAuthorized); }
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        let key = DataKey::Dispute(contract_id);
+        let dispute: Dispute = env.storage().persistent().get(&key).ok_or(LifecycleError::DisputeNotFound)?;
+        if employee_amount < 0 || employee_amount > dispute.amount { return Err(LifecycleError::InvalidAmount); }
+
+        if dispute.milestone_id.is_none() {
+            let advance = Self::get_outstanding_advance(env.clone(), contract_id);
+            Self::recover_advance(&env, contract_id, advance);
+            contract.paid_amount += advance;
+        }
+
+        if employee_amount > 0 {
+            Self::pay_employee(&env, &contract, employee_amount, PaymentKind::DisputeResolution, dispute.milestone_id, None)?;
+        }
+
+        // The award settles the milestone; it cannot be paid again.
+        if let Some(milestone_id) = dispute.milestone_id {
+            if !Self::find_milestone(&contract, milestone_id)?.completed {
+                Self::mark_milestone_completed(&env, &mut contract, milestone_id)?;
+            }
+            Self::mark_milestone_paid(&env, contract_id, milestone_id);
+        }
+
+        let employer_refund = dispute.amount - employee_amount;
+        Self::release_funds(&env, contract_id, employer_refund)?;
+        contract.paid_amount += employee_amount;
+        contract.total_amount -= employer_refund;
+        contract.status = if contract.paid_amount >= contract.total_amount {
+            Self::release_keeper_pool(&env, contract_id)?;
+            // Only a request still waiting for approval can be open here.
+            Self::write_off_advance(&env, contract_id);
+            ContractStatus::Completed
+        } else {
+            ContractStatus::Active
+        };
+
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        env.storage().persistent().remove(&key);
+
+        env.events().publish(
+            (symbol_short!("RESOLVED"), contract_id),
+            (arbiter, employee_amount, employer_refund),
+        );
+
+        Ok(())
+    }
+
+    pub fn get_dispute(env: Env, contract_id: u32) -> Option<Dispute> {
+        env.storage().persistent().get(&DataKey::Dispute(contract_id))
+    }
+
+    /// Points the lifecycle at the identity contract whose managers may arbitrate.
+    pub fn set_identity_contract(env: Env, admin: Address, identity_address: Address) -> Result<(), LifecycleError> {
+        admin.require_auth();
+        Self::ensure_initialized(&env)?;
+        if admin != Self::get_admin(&env)? { return Err(LifecycleError::NotAuthorized); }
+
+        env.storage().instance().set(&DataKey::IdentityAddress, &identity_address);
+        Ok(())
+    }
+
+    // ====================================================================
+    // Suspensions
+    // ====================================================================
+
+    /// Puts an Active contract on hold. No payments can be made and schedules
+    /// and streams stop accruing until it is resumed. The employer or an
+    /// arbiter may suspend.
+    pub fn suspend_contract(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        reason: String,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && !Self::is_arbiter(&env, &caller)? { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let suspension = Suspension {
+            suspended_by: caller.clone(),
+            reason: reason.clone(),
+            suspended_at: env.ledger().timestamp(),
+        };
+
+        contract.status = ContractStatus::OnHold;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+        Self::persist(&env, &DataKey::Suspension(contract_id), &suspension);
+
+        env.events().publish((symbol_short!("SUSPEND"), contract_id), (caller, reason));
+        Ok(())
+    }
+
+    /// Takes a contract off hold. The time spent suspended is added to the
+    /// schedule, stream and end date, so the held period is neither paid nor lost.
+    pub fn resume_contract(env: Env, caller: Address, contract_id: u32) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && !Self::is_arbiter(&env, &caller)? { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::OnHold { return Err(LifecycleError::ContractNotOnHold); }
+
+        let held_for = Self::lift_suspension(&env, &mut contract)?;
+        contract.status = ContractStatus::Active;
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+
+        env.events().publish((symbol_short!("RESUME"), contract_id), (caller, held_for));
+        Ok(())
+    }
+
+    pub fn get_suspension(env: Env, contract_id: u32) -> Option<Suspension> {
+        env.storage().persistent().get(&DataKey::Suspension(contract_id))
+    }
+
+    // ====================================================================
+    // Team Payees
+    // ====================================================================
+
+    /// Turns a Draft contract into a team contract whose payouts are split
+    /// between `payees` instead of going to the employee alone. The employee
+    /// accepts on the team's behalf. An empty list reverts to a single payee.
+    pub fn set_payees(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        payees: Vec<Payee>,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+
+        if payees.is_empty() {
+            env.storage().persistent().remove(&DataKey::Payees(contract_id));
+            return Ok(());
+        }
+        if payees.len() > MAX_PAYEES { return Err(LifecycleError::InvalidSplitRule); }
+
+        let mut seen = Vec::new(&env);
+        let mut percent_total: u32 = 0;
+        let mut fixed_total: i128 = 0;
+        for payee in payees.iter() {
+            if seen.contains(&payee.address) { return Err(LifecycleError::InvalidSplitRule); }
+            seen.push_back(payee.address.clone());
+
+            match payee.rule {
+                SplitRule::Percent(bps) => {
+                    if bps == 0 || bps > BASIS_POINTS { return Err(LifecycleError::InvalidSplitRule); }
+                    percent_total += bps;
+                }
+                SplitRule::Fixed(amount) => {
+                    if amount <= 0 { return Err(LifecycleError::InvalidSplitRule); }
+                    fixed_total += amount;
+                }
+            }
+            Self::ensure_employee_can_receive_payment(&env, &payee.address, &contract.asset)?;
+        }
+        if percent_total != 0 && percent_total != BASIS_POINTS { return Err(LifecycleError::InvalidSplitRule); }
+        if fixed_total > contract.total_amount { return Err(LifecycleError::InvalidSplitRule); }
+
+        Self::persist(&env, &DataKey::Payees(contract_id), &payees);
+        Ok(())
+    }
+
+    /// Payees of a team contract; empty when the employee is paid alone.
+    pub fn get_payees(env: Env, contract_id: u32) -> Vec<Payee> {
+        env.storage().persistent().get(&DataKey::Payees(contract_id)).unwrap_or(Vec::new(&env))
+    }
+
+    // ====================================================================
+    // Deductions
+    // ====================================================================
+
+    /// Sets the deductions withheld from every payout of a Draft contract, so
+    /// the employee agrees to them on acceptance. Percentages may add up to at
+    /// most 100%. An empty list removes all deductions.
+    pub fn set_deductions(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        deductions: Vec<Deduction>,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+        if deductions.len() > MAX_DEDUCTIONS { return Err(LifecycleError::InvalidSplitRule); }
+
+        let mut percent_total: u32 = 0;
+        for deduction in deductions.iter() {
+            match deduction.rule {
+                SplitRule::Percent(bps) => {
+                    if bps == 0 { return Err(LifecycleError::InvalidSplitRule); }
+                    percent_total += bps;
+                }
+                SplitRule::Fixed(amount) => {
+                    if amount <= 0 { return Err(LifecycleError::InvalidSplitRule); }
+                }
+            }
+        }
+        if percent_total > BASIS_POINTS { return Err(LifecycleError::InvalidSplitRule); }
+
+        if deductions.is_empty() {
+            env.storage().persistent().remove(&DataKey::Deductions(contract_id));
+        } else {
+            Self::persist(&env, &DataKey::Deductions(contract_id), &deductions);
+        }
+        Ok(())
+    }
+
+    pub fn get_deductions(env: Env, contract_id: u32) -> Vec<Deduction> {
+        env.storage().persistent().get(&DataKey::Deductions(contract_id)).unwrap_or(Vec::new(&env))
+    }
+
+    // ====================================================================
+    // Timesheets
+    // ====================================================================
+
+    /// Makes a Draft PayAsYouGo contract hourly. From then on it is paid
+    /// through timesheets at `rate` per hour instead of ad-hoc amounts.
+    pub fn set_hourly_rate(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        rate: i128,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.contract_type != ContractType::PayAsYouGo { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+        if env.storage().persistent().has(&DataKey::UsageTerms(contract_id)) { return Err(LifecycleError::InvalidContractType); }
+        if rate <= 0 { return Err(LifecycleError::InvalidAmount); }
+
+        Self::persist(&env, &DataKey::HourlyRate(contract_id), &rate);
+        Ok(())
+    }
+
+    pub fn get_hourly_rate(env: Env, contract_id: u32) -> Option<i128> {
+        env.storage().persistent().get(&DataKey::HourlyRate(contract_id))
+    }
+
+    /// Worker logs `hours` for `period`. A rejected timesheet may be
+    /// resubmitted; any other existing timesheet for the period blocks it.
+    pub fn submit_timesheet(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+        period: u32,
+        hours: u32,
+        reference: BytesN<32>,
+    ) -> Result<(), LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        Self::get_hourly_rate(env.clone(), contract_id).ok_or(LifecycleError::HourlyRateNotSet)?;
+        if hours == 0 { return Err(LifecycleError::InvalidAmount); }
+
+        let key = DataKey::Timesheet(contract_id, period);
+        let existing: Option<Timesheet> = env.storage().persistent().get(&key);
+        if matches!(existing, Some(timesheet) if timesheet.status != TimesheetStatus::Rejected) {
+            return Err(LifecycleError::InvalidTimesheetState);
+        }
+
+        let timesheet = Timesheet {
+            period,
+            hours,
+            reference,
+            status: TimesheetStatus::Submitted,
+            submitted_at: env.ledger().timestamp(),
+            reviewed_at: None,
+        };
+        Self::persist(&env, &key, &timesheet);
+
+        let mut open = Self::open_timesheets(&env, contract_id);
+        if !open.contains(period) {
+            open.push_back(period);
+            Self::persist(&env, &DataKey::OpenTimesheets(contract_id), &open);
+        }
+
+        env.events().publish((symbol_short!("TS_SUBMIT"), contract_id), (period, hours));
+        Ok(())
+    }
+
+    pub fn approve_timesheet(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        period: u32,
+    ) -> Result<(), LifecycleError> {
+        Self::review_timesheet(&env, &employer, contract_id, period, TimesheetStatus::Approved)?;
+        env.events().publish((symbol_short!("TS_APPROV"), contract_id), period);
+        Ok(())
+    }
+
+    pub fn reject_timesheet(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        period: u32,
+    ) -> Result<(), LifecycleError> {
+        Self::review_timesheet(&env, &employer, contract_id, period, TimesheetStatus::Rejected)?;
+        env.events().publish((symbol_short!("TS_REJECT"), contract_id), period);
+        Ok(())
+    }
+
+    /// Pays an approved timesheet at the contract's hourly rate and marks it
+    /// paid so the same hours cannot be paid again. Returns the amount paid.
+    pub fn pay_timesheet(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        period: u32,
+    ) -> Result<i128, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        let delegation = Self::authorize_employer(&env, &contract.employer, &caller, OperatorScope::ProcessPayments)?;
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let key = DataKey::Timesheet(contract_id, period);
+        let mut timesheet: Timesheet = env.storage().persistent().get(&key).ok_or(LifecycleError::TimesheetNotFound)?;
+        if timesheet.status != TimesheetStatus::Approved { return Err(LifecycleError::InvalidTimesheetState); }
+
+        let rate = Self::get_hourly_rate(env.clone(), contract_id).ok_or(LifecycleError::HourlyRateNotSet)?;
+        let amount = rate * timesheet.hours as i128;
+        if amount > contract.total_amount - contract.paid_amount { return Err(LifecycleError::InsufficientContractFunds); }
+
+        Self::charge_operator(&env, &contract.employer, &caller, delegation, amount)?;
+        Self::pay_employee(&env, &contract, amount, PaymentKind::Timesheet, None, None)?;
+
+        Self::mark_timesheet_paid(&env, contract_id, &mut timesheet);
+
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+        }
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+
+        env.events().publish(
+            (symbol_short!("PAYG"), contract_id),
+            (caller, contract.employee, amount),
+        );
+
+        Ok(amount)
+    }
+
+    pub fn get_timesheet(env: Env, contract_id: u32, period: u32) -> Option<Timesheet> {
+        env.storage().persistent().get(&DataKey::Timesheet(contract_id, period))
+    }
+
+    // ====================================================================
+    // Usage Reports
+    // ====================================================================
+
+    /// Makes a Draft PayAsYouGo contract metered. The employer proposes the
+    /// reporter and pricing; the employee agrees to them by accepting the offer.
+    pub fn set_usage_terms(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        terms: UsageTerms,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.contract_type != ContractType::PayAsYouGo { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Draft { return Err(LifecycleError::ContractNotDraft); }
+        if env.storage().persistent().has(&DataKey::HourlyRate(contract_id)) { return Err(LifecycleError::InvalidContractType); }
+        if terms.unit_price <= 0 { return Err(LifecycleError::InvalidAmount); }
+        if terms.reporter == contract.employer || terms.reporter == contract.employee {
+            return Err(LifecycleError::NotAuthorized);
+        }
+
+        Self::persist(&env, &DataKey::UsageTerms(contract_id), &terms);
+        Ok(())
+    }
+
+    pub fn get_usage_terms(env: Env, contract_id: u32) -> Option<UsageTerms> {
+        env.storage().persistent().get(&DataKey::UsageTerms(contract_id))
+    }
+
+    /// The contract's reporter files usage for `period`. Each period is
+    /// reported once; only a challenged report may be replaced.
+    pub fn report_usage(
+        env: Env,
+        reporter: Address,
+        contract_id: u32,
+        period: u32,
+        units: u32,
+        reference: BytesN<32>,
+    ) -> Result<(), LifecycleError> {
+        reporter.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        let terms = Self::get_usage_terms(env.clone(), contract_id).ok_or(LifecycleError::UsageTermsNotSet)?;
+        if terms.reporter != reporter { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if units == 0 { return Err(LifecycleError::InvalidAmount); }
+
+        let key = DataKey::UsageReport(contract_id, period);
+        let existing: Option<UsageReport> = env.storage().persistent().get(&key);
+        if matches!(existing, Some(report) if report.status != UsageStatus::Challenged) {
+            return Err(LifecycleError::DuplicateUsageReport);
+        }
+
+        let report = UsageReport {
+            period,
+            units,
+            reference,
+            status: UsageStatus::Pending,
+            reported_at: env.ledger().timestamp(),
+            challenged_by: None,
+        };
+        Self::persist(&env, &key, &report);
+
+        let mut open = Self::open_usage_reports(&env, contract_id);
+        if !open.contains(period) {
+            open.push_back(period);
+            Self::persist(&env, &DataKey::OpenUsageReports(contract_id), &open);
+        }
+
+        env.events().publish((symbol_short!("USAGE"), contract_id), (period, units));
+        Ok(())
+    }
+
+    /// Either party disputes a pending report while its challenge window is
+    /// open. The report will not be paid until the reporter files a correction.
+    pub fn challenge_usage(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        period: u32,
+    ) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != contract.employee { return Err(LifecycleError::NotAuthorized); }
+        let terms = Self::get_usage_terms(env.clone(), contract_id).ok_or(LifecycleError::UsageTermsNotSet)?;
+
+        let key = DataKey::UsageReport(contract_id, period);
+        let mut report: UsageReport = env.storage().persistent().get(&key).ok_or(LifecycleError::UsageReportNotFound)?;
+        if report.status != UsageStatus::Pending { return Err(LifecycleError::DuplicateUsageReport); }
+        if env.ledger().timestamp() >= report.reported_at + terms.challenge_window {
+            return Err(LifecycleError::ChallengeWindowClosed);
+        }
+
+        report.status = UsageStatus::Challenged;
+        report.challenged_by = Some(caller.clone());
+        Self::persist(&env, &key, &report);
+
+        env.events().publish((symbol_short!("USAGE_CHL"), contract_id), (caller, period));
+        Ok(())
+    }
+
+    /// Pays an unchallenged report once its challenge window has passed, at
+    /// units × unit price. Either party may trigger it. Returns the amount paid.
+    pub fn pay_usage(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        period: u32,
+    ) -> Result<i128, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != contract.employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        let terms = Self::get_usage_terms(env.clone(), contract_id).ok_or(LifecycleError::UsageTermsNotSet)?;
+
+        let key = DataKey::UsageReport(contract_id, period);
+        let mut report: UsageReport = env.storage().persistent().get(&key).ok_or(LifecycleError::UsageReportNotFound)?;
+        if report.status != UsageStatus::Pending { return Err(LifecycleError::DuplicateUsageReport); }
+        if env.ledger().timestamp() < report.reported_at + terms.challenge_window {
+            return Err(LifecycleError::ChallengeWindowOpen);
+        }
+
+        let amount = terms.unit_price * report.units as i128;
+        if amount > contract.total_amount - contract.paid_amount { return Err(LifecycleError::InsufficientContractFunds); }
+
+        Self::pay_employee(&env, &contract, amount, PaymentKind::Usage, None, None)?;
+
+        Self::mark_usage_paid(&env, contract_id, &mut report);
+
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+        }
+        Self::persist(&env, &DataKey::Contract(contract_id), &contract);
+
+        env.events().publish(
+            (symbol_short!("PAYG"), contract_id),
+            (contract.employer, contract.employee, amount),
+        );
+
+        Ok(amount)
+    }
+
+    pub fn get_usage_report(env: Env, contract_id: u32, period: u32) -> Option<UsageReport> {
+        env.storage().persistent().get(&DataKey::UsageReport(contract_id, period))
+    }
+
+    // ====================================================================
+    // Keeper Payments
+    // ====================================================================
+
+    /// Pre-authorizes keepers to run a scheduled FixedRate contract's
+    /// payments once they fall due, paying `reward` to the keeper each time.
+    /// The employer funds a reward for every unpaid period up front. A zero
+    /// reward withdraws the authorization and releases what is left.
+    pub fn set_keeper_reward(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        reward: i128,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Draft && contract.status != ContractStatus::Active {
+            return Err(LifecycleError::ContractNotActive);
+        }
+        if reward < 0 { return Err(LifecycleError::InvalidAmount); }
+
+        let schedule: PaymentSchedule = env.storage()
+            .persistent()
+            .get(&DataKey::Schedule(contract_id))
+            .ok_or(LifecycleError::InvalidSchedule)?;
+
+        Self::release_keeper_pool(&env, contract_id)?;
+        if reward > 0 {
+            let pool = reward * (Self::total_periods(&schedule) - schedule.periods_paid) as i128;
+            Self::reserve_funds(&env, contract_id, &contract.asset, pool)?;
+            Self::persist(&env, &DataKey::KeeperTerms(contract_id), &KeeperTerms { reward, reserved: pool });
+        }
+
+        env.events().publish((symbol_short!("KEEPER_RW"), contract_id), reward);
+        Ok(())
+    }
+
+    pub fn get_keeper_terms(env: Env, contract_id: u32) -> Option<KeeperTerms> {
+        env.storage().persistent().get(&DataKey::KeeperTerms(contract_id))
+    }
+
+    /// Runs a contract's due scheduled payment on the employer's standing
+    /// authorization. Anyone may call it; the keeper earns the reward, and
+    /// nothing is paid unless a period has fallen due.
+    pub fn execute_scheduled_payment(
+        env: Env,
+        keeper: Address,
+        contract_id: u32,
+    ) -> Result<u32, LifecycleError> {
+        keeper.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut terms = Self::get_keeper_terms(env.clone(), contract_id).ok_or(LifecycleError::KeeperPaymentsDisabled)?;
+        let mut contract = Self::get_contract_internal(&env, contract_id)?;
+
+        // The reward comes first: completing the schedule releases the pool.
+        let reward = terms.reward.min(terms.reserved);
+        if reward > 0 {
+            Self::pay_from_reservation(&env, contract_id, &keeper, reward)?;
+            terms.reserved -= reward;
+            Self::persist(&env, &DataKey::KeeperTerms(contract_id), &terms);
+        }
+
+        let (periods, _) = Self::pay_scheduled(&env, &mut contract)?;
+
+        env.events().publish((symbol_short!("KEEPER"), contract_id), (keeper, periods, reward));
+        Ok(periods)
+    }
+
+    // ====================================================================
+    // Payroll Runs
+    // ====================================================================
+    /// Pays many contracts in one call. Each item is checked as the single
+    /// payment entrypoints would; the vault is then drawn on once per asset.
+    /// Returns the run id and one outcome per item, in order.
+    pub fn run_payroll(
+        env: Env,
+        caller: Address,
+        items: Vec<PayrollItem>,
+        mode: PayrollMode,
+    ) -> Result<PayrollReport, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+        if items.is_empty() || items.len() > MAX_PAYROLL_ITEMS { return Err(LifecycleError::InvalidPayrollRun); }
+
+        let run_id = Self::next_payroll_run_id(&env);
+        let outcomes = Self::pay_payroll(&env, &caller, run_id, &items, &mode)?;
+        Ok(PayrollReport { run_id, outcomes })
+    }
+
+    /// Sets who must sign off on the employer's payroll runs. Runs already
+    /// drafted keep the approvers they were drafted under.
+    pub fn set_payroll_approvers(
+        env: Env,
+        employer: Address,
+        approvers: Vec<Address>,
+        threshold: u32,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        if threshold == 0 || threshold > approvers.len() { return Err(LifecycleError::InvalidPayrollRun); }
+        for (index, approver) in approvers.iter().enumerate() {
+            if approvers.first_index_of(&approver) != Some(index as u32) { return Err(LifecycleError::InvalidPayrollRun); }
+        }
+
+        let policy = PayrollApprovers { approvers, threshold };
+        Self::persist(&env, &DataKey::PayrollApprovers(employer.clone()), &policy);
+
+        env.events().publish((symbol_short!("RUN_APPRS"), employer), policy);
+        Ok(())
+    }
+
+    pub fn get_payroll_approvers(env: Env, employer: Address) -> Option<PayrollApprovers> {
+        env.storage().persistent().get(&DataKey::PayrollApprovers(employer))
+    }
+
+    /// Drafts a payroll run for `employer` to be approved under the
+    /// employer's payroll approvers before it can execute. The preparer, the
+    /// employer or one of their payment operators, may not be one of those
+    /// approvers, so nobody signs off on their own run.
+    pub fn draft_payroll_run(
+        env: Env,
+        preparer: Address,
+        employer: Address,
+        items: Vec<PayrollItem>,
+    ) -> Result<u32, LifecycleError> {
+        preparer.require_auth();
+        Self::ensure_initialized(&env)?;
+        Self::authorize_employer(&env, &employer, &preparer, OperatorScope::ProcessPayments)?;
+
+        let PayrollApprovers { approvers, threshold } = Self::get_payroll_approvers(env.clone(), employer.clone())
+            .ok_or(LifecycleError::InvalidPayrollRun)?;
+        if approvers.contains(&preparer) { return Err(LifecycleError::NotAuthorized); }
+
+        let (lines, totals) = Self::snapshot_payroll(&env, &employer, &items)?;
+        let id = Self::next_payroll_run_id(&env);
+        let run = PayrollRun {
+            id,
+            employer,
+            preparer,
+            lines,
+            totals: totals.clone(),
+            approvers,
+            threshold,
+            approvals: Vec::new(&env),
+            status: PayrollRunStatus::Draft,
+            created_at: env.ledger().timestamp(),
+        };
+        Self::persist(&env, &DataKey::PayrollRun(id), &run);
+
+        env.events().publish((symbol_short!("RUN_DRAFT"), id), totals);
+        Ok(id)
+    }
+
+    /// Replaces the payments of a run that has not executed yet. Approvals
+    /// given so far are dropped, since they were for the old snapshot.
+    pub fn update_payroll_run(
+        env: Env,
+        preparer: Address,
+        run_id: u32,
+        items: Vec<PayrollItem>,
+    ) -> Result<(), LifecycleError> {
+        preparer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut run = Self::get_open_payroll_run(&env, run_id)?;
+        if run.preparer != preparer { return Err(LifecycleError::NotAuthorized); }
+
+        let (lines, totals) = Self::snapshot_payroll(&env, &run.employer, &items)?;
+        run.lines = lines;
+        run.totals = totals.clone();
+        run.approvals = Vec::new(&env);
+        run.status = PayrollRunStatus::Draft;
+        Self::persist(&env, &DataKey::PayrollRun(run_id), &run);
+
+        env.events().publish((symbol_short!("RUN_EDIT"), run_id), totals);
+        Ok(())
+    }
+
+    /// Signs off on a run's current snapshot. The run is Approved once
+    /// `threshold` approvers have done so.
+    pub fn approve_payroll_run(env: Env, approver: Address, run_id: u32) -> Result<(), LifecycleError> {
+        approver.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut run = Self::get_open_payroll_run(&env, run_id)?;
+        if !run.approvers.contains(&approver) { return Err(LifecycleError::NotAuthorized); }
+        Self::authorize_employer(&env, &run.employer, &approver, OperatorScope::ProcessPayments)?;
+        if run.approvals.contains(&approver) { return Err(LifecycleError::InvalidPayrollRunState); }
+
+        run.approvals.push_back(approver.clone());
+        if run.approvals.len() >= run.threshold {
+            run.status = PayrollRunStatus::Approved;
+        }
+        Self::persist(&env, &DataKey::PayrollRun(run_id), &run);
+
+        env.events().publish((symbol_short!("RUN_APPR"), run_id), (approver, run.approvals.len()));
+        Ok(())
+    }
+
+    /// Pays an approved run exactly as snapshotted. The run fails as a whole
+    /// if any payment can no longer be made as approved.
+    pub fn execute_payroll_run(env: Env, caller: Address, run_id: u32) -> Result<PayrollReport, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut run = Self::get_payroll_run(env.clone(), run_id).ok_or(LifecycleError::PayrollRunNotFound)?;
+        Self::authorize_employer(&env, &run.employer, &caller, OperatorScope::ProcessPayments)?;
+        if run.status != PayrollRunStatus::Approved { return Err(LifecycleError::InvalidPayrollRunState); }
+
+        let mut items = Vec::new(&env);
+        for line in run.lines.iter() {
+            let contract = Self::get_contract_internal(&env, line.contract_id)?;
+            if contract.employee != line.payee || contract.asset != line.asset {
+                return Err(LifecycleError::InvalidPayrollRun);
+            }
+            items.push_back(PayrollItem { contract_id: line.contract_id, amount: PayrollAmount::Amount(line.amount) });
+        }
+
+        let outcomes = Self::pay_payroll(&env, &caller, run_id, &items, &PayrollMode::AllOrNothing)?;
+        run.status = PayrollRunStatus::Executed;
+        Self::persist(&env, &DataKey::PayrollRun(run_id), &run);
+
+        Ok(PayrollReport { run_id, outcomes })
+    }
+
+    pub fn cancel_payroll_run(env: Env, caller: Address, run_id: u32) -> Result<(), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let mut run = Self::get_open_payroll_run(&env, run_id)?;
+        if caller != run.preparer && caller != run.employer { return Err(LifecycleError::NotAuthorized); }
+
+        run.status = PayrollRunStatus::Cancelled;
+        Self::persist(&env, &DataKey::PayrollRun(run_id), &run);
+
+        env.events().publish((symbol_short!("RUN_CANCL"), run_id), caller);
+        Ok(())
+    }
+
+    pub fn get_payroll_run(env: Env, run_id: u32) -> Option<PayrollRun> {
+        env.storage().persistent().get(&DataKey::PayrollRun(run_id))
+    }
+
+    // ====================================================================
+    // Salary Advances
+    // ====================================================================
+
+    /// Opens a FixedRate or PayAsYouGo contract to salary advances, or
+    /// changes how much may be advanced.
+    pub fn set_advance_policy(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        policy: AdvancePolicy,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.contract_type != ContractType::FixedRate && contract.contract_type != ContractType::PayAsYouGo {
+            return Err(LifecycleError::InvalidContractType);
+        }
+        if policy.max_share_bps == 0 || policy.max_share_bps > BASIS_POINTS || policy.auto_approve_limit < 0 {
+            return Err(LifecycleError::InvalidAmount);
+        }
+
+        Self::persist(&env, &DataKey::AdvancePolicy(contract_id), &policy);
+        Ok(())
+    }
+
+    pub fn get_advance_policy(env: Env, contract_id: u32) -> Option<AdvancePolicy> {
+        env.storage().persistent().get(&DataKey::AdvancePolicy(contract_id))
+    }
+
+    /// Requests an advance on an active contract and returns its id. Only one
+    /// advance may be open at a time. Requests within the policy's
+    /// auto-approve limit are paid immediately.
+    pub fn request_advance(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+        amount: i128,
+    ) -> Result<u32, LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if amount <= 0 { return Err(LifecycleError::InvalidAmount); }
+
+        let policy = Self::get_advance_policy(env.clone(), contract_id).ok_or(LifecycleError::AdvanceLimitExceeded)?;
+        if Self::open_advance(&env, contract_id).is_some() { return Err(LifecycleError::AdvanceLimitExceeded); }
+        Self::ensure_within_advance_limit(&contract, &policy, amount)?;
+
+        let id = Self::get_advance_count(env.clone(), contract_id);
+        let mut advance = Advance {
+            id,
+            amount,
+            outstanding: 0,
+            status: AdvanceStatus::Requested,
+            requested_at: env.ledger().timestamp(),
+        };
+        Self::persist(&env, &DataKey::AdvanceCount(contract_id), &(id + 1));
+        env.events().publish((symbol_short!("ADV_REQ"), contract_id), (id, amount));
+
+        if amount <= policy.auto_approve_limit {
+            Self::disburse_advance(&env, &contract, &mut advance)?;
+        }
+        Self::persist(&env, &DataKey::Advance(contract_id, id), &advance);
+        Ok(id)
+    }
+
+    pub fn approve_advance(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        advance_id: u32,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let mut advance = Self::pending_advance(&env, contract_id, advance_id)?;
+        // Payouts since the request may have lowered the limit.
+        let policy = Self::get_advance_policy(env.clone(), contract_id).ok_or(LifecycleError::AdvanceLimitExceeded)?;
+        Self::ensure_within_advance_limit(&contract, &policy, advance.amount)?;
+
+        Self::disburse_advance(&env, &contract, &mut advance)?;
+        Self::persist(&env, &DataKey::Advance(contract_id, advance_id), &advance);
+        Ok(())
+    }
+
+    pub fn reject_advance(
+        env: Env,
+        employer: Address,
+        contract_id: u32,
+        advance_id: u32,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employer != employer { return Err(LifecycleError::NotAuthorized); }
+
+        let mut advance = Self::pending_advance(&env, contract_id, advance_id)?;
+        advance.status = AdvanceStatus::Rejected;
+        Self::persist(&env, &DataKey::Advance(contract_id, advance_id), &advance);
+
+        env.events().publish((symbol_short!("ADV_REJ"), contract_id), advance_id);
+        Ok(())
+    }
+
+    pub fn get_advance(env: Env, contract_id: u32, advance_id: u32) -> Option<Advance> {
+        env.storage().persistent().get(&DataKey::Advance(contract_id, advance_id))
+    }
+
+    pub fn get_advances(env: Env, contract_id: u32) -> Vec<Advance> {
+        let mut advances = Vec::new(&env);
+        for advance_id in 0..Self::get_advance_count(env.clone(), contract_id) {
+            if let Some(advance) = Self::get_advance(env.clone(), contract_id, advance_id) {
+                advances.push_back(advance);
+            }
+        }
+        advances
+    }
+
+    pub fn get_advance_count(env: Env, contract_id: u32) -> u32 {
+        env.storage().persistent().get(&DataKey::AdvanceCount(contract_id)).unwrap_or(0)
+    }
+
+    /// What later payouts of the contract still have to recover.
+    pub fn get_outstanding_advance(env: Env, contract_id: u32) -> i128 {
+        Self::open_advance(&env, contract_id).map(|advance| advance.outstanding).unwrap_or(0)
+    }
+
+    // ====================================================================
+    // Expense Claims
+    // ====================================================================
+
+    /// Lets `manager` approve and reject expense claims on all of the
+    /// employer's contracts, or revokes that right.
+    pub fn set_claim_approver(
+        env: Env,
+        employer: Address,
+        manager: Address,
+        allowed: bool,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let key = DataKey::ClaimApprover(employer.clone(), manager.clone());
+        if allowed {
+            Self::persist(&env, &key, &true);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        env.events().publish((symbol_short!("CLM_APPR"), employer), (manager, allowed));
+        Ok(())
+    }
+
+    pub fn is_claim_approver(env: Env, employer: Address, manager: Address) -> bool {
+        env.storage().persistent().has(&DataKey::ClaimApprover(employer, manager))
+    }
+
+    /// Files an expense claim against an active contract and returns its id.
+    pub fn submit_claim(
+        env: Env,
+        employee: Address,
+        contract_id: u32,
+        amount: i128,
+        category: Symbol,
+        receipt: BytesN<32>,
+    ) -> Result<u32, LifecycleError> {
+        employee.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if contract.employee != employee { return Err(LifecycleError::NotAuthorized); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        if amount <= 0 { return Err(LifecycleError::InvalidAmount); }
+
+        let id = Self::get_claim_count(env.clone(), contract_id);
+        let claim = ExpenseClaim {
+            id,
+            amount,
+            category: category.clone(),
+            receipt,
+            status: ClaimStatus::Submitted,
+            history: Vec::from_array(&env, [ClaimStatusChange {
+                status: ClaimStatus::Submitted,
+                actor: employee,
+                timestamp: env.ledger().timestamp(),
+            }]),
+        };
+        Self::persist(&env, &DataKey::Claim(contract_id, id), &claim);
+        Self::persist(&env, &DataKey::ClaimCount(contract_id), &(id + 1));
+
+        env.events().publish((symbol_short!("CLAIM"), contract_id), (id, amount, category));
+        Ok(id)
+    }
+
+    /// Approves a submitted claim on an active contract and reimburses the
+    /// employee straight away. The payment comes out of the vault's unallocated liquidity and does
+    /// not count towards the contract's `paid_amount`.
+    pub fn approve_claim(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        claim_id: u32,
+    ) -> Result<(), LifecycleError> {
+        let (contract, mut claim) = Self::review_claim(&env, &caller, contract_id, claim_id, ClaimStatus::Approved)?;
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        Self::reserve_funds(&env, contract_id, &contract.asset, claim.amount)?;
+        Self::pay_from_reservation(&env, contract_id, &contract.employee, claim.amount)?;
+
+        claim.status = ClaimStatus::Paid;
+        claim.history.push_back(ClaimStatusChange {
+            status: ClaimStatus::Paid,
+            actor: caller,
+            timestamp: env.ledger().timestamp(),
+        });
+        Self::persist(&env, &DataKey::Claim(contract_id, claim_id), &claim);
+
+        env.events().publish((symbol_short!("CLM_PAID"), contract_id), (claim_id, claim.amount));
+        Ok(())
+    }
+
+    pub fn reject_claim(
+        env: Env,
+        caller: Address,
+        contract_id: u32,
+        claim_id: u32,
+    ) -> Result<(), LifecycleError> {
+        let (_, claim) = Self::review_claim(&env, &caller, contract_id, claim_id, ClaimStatus::Rejected)?;
+        Self::persist(&env, &DataKey::Claim(contract_id, claim_id), &claim);
+
+        env.events().publish((symbol_short!("CLM_REJ"), contract_id), claim_id);
+        Ok(())
+    }
+
+    pub fn get_claim(env: Env, contract_id: u32, claim_id: u32) -> Option<ExpenseClaim> {
+        env.storage().persistent().get(&DataKey::Claim(contract_id, claim_id))
+    }
+
+    pub fn get_claim_history(env: Env, contract_id: u32, claim_id: u32) -> Vec<ClaimStatusChange> {
+        Self::get_claim(env.clone(), contract_id, claim_id)
+            .map(|claim| claim.history)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn get_claim_count(env: Env, contract_id: u32) -> u32 {
+        env.storage().persistent().get(&DataKey::ClaimCount(contract_id)).unwrap_or(0)
+    }
+
+    // ====================================================================
+    // Storage
+    // ====================================================================
+
+    /// Extends the TTL of a contract and every record that belongs to it,
+    /// including both parties' contract lists and any timesheets or usage
+    /// reports still awaiting payment. Payment history and settled work are
+    /// extended page by page through `bump_contract_history`. Anyone may call
+    /// this.
+    pub fn bump_contract(env: Env, contract_id: u32) -> Result<(), LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+
+        let mut keys = Vec::from_array(&env, [
+            DataKey::Contract(contract_id),
+            DataKey::Schedule(contract_id),
+            DataKey::Stream(contract_id),
+            DataKey::Termination(contract_id),
+            DataKey::HourlyRate(contract_id),
+            DataKey::UsageTerms(contract_id),
+        ]);
+        keys.push_back(DataKey::PendingAmendment(contract_id));
+        keys.push_back(DataKey::Dispute(contract_id));
+        keys.push_back(DataKey::Suspension(contract_id));
+        keys.push_back(DataKey::Payees(contract_id));
+        keys.push_back(DataKey::PaidMilestones(contract_id));
+        keys.push_back(DataKey::Deductions(contract_id));
+        keys.push_back(DataKey::PaymentCount(contract_id));
+        keys.push_back(DataKey::ClaimCount(contract_id));
+        keys.push_back(DataKey::KeeperTerms(contract_id));
+        keys.push_back(DataKey::AdvancePolicy(contract_id));
+        keys.push_back(DataKey::AdvanceCount(contract_id));
+        for advance_id in 0..Self::get_advance_count(env.clone(), contract_id) {
+            keys.push_back(DataKey::Advance(contract_id, advance_id));
+        }
+        for claim_id in 0..Self::get_claim_count(env.clone(), contract_id) {
+            keys.push_back(DataKey::Claim(contract_id, claim_id));
+        }
+        keys.push_back(DataKey::EmployerContracts(contract.employer.clone()));
+        keys.push_back(DataKey::EmployeeContracts(contract.employee.clone()));
+        for version in 1..contract.version {
+            keys.push_back(DataKey::ContractVersion(contract_id, version));
+        }
+        for milestone in contract.milestones.unwrap_or(Vec::new(&env)).iter() {
+            keys.push_back(DataKey::MilestoneHistory(contract_id, milestone.id));
+        }
+        keys.push_back(DataKey::OpenTimesheets(contract_id));
+        for period in Self::open_timesheets(&env, contract_id).iter() {
+            keys.push_back(DataKey::Timesheet(contract_id, period));
+        }
+        keys.push_back(DataKey::OpenUsageReports(contract_id));
+        for period in Self::open_usage_reports(&env, contract_id).iter() {
+            keys.push_back(DataKey::UsageReport(contract_id, period));
+        }
+
+        for key in keys.iter() {
+            Self::extend_record(&env, &key);
+        }
+        Ok(())
+    }
+
+    /// Extends the TTL of the payment records with sequence numbers from
+    /// `cursor`, and of the timesheets and usage reports for the same range
+    /// of periods. Covers at most `MAX_PAGE_SCAN` entries per call; returns
+    /// where the next call should start.
+    pub fn bump_contract_history(env: Env, contract_id: u32, cursor: u32, limit: u32) -> Result<u32, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        Self::get_contract_internal(&env, contract_id)?;
+
+        let end = cursor.saturating_add(limit.min(MAX_PAGE_SCAN));
+        let payments_end = Self::get_payment_count(env.clone(), contract_id).min(end);
+        for seq in cursor..payments_end {
+            Self::extend_record(&env, &DataKey::Payment(contract_id, seq));
+        }
+        for period in cursor..end {
+            Self::extend_record(&env, &DataKey::Timesheet(contract_id, period));
+            Self::extend_record(&env, &DataKey::UsageReport(contract_id, period));
+        }
+        Ok(end)
+    }
+
+    /// Extends the TTL of a payroll run. Anyone may call this.
+    pub fn bump_payroll_run(env: Env, run_id: u32) -> Result<(), LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let key = DataKey::PayrollRun(run_id);
+        if !env.storage().persistent().has(&key) { return Err(LifecycleError::PayrollRunNotFound); }
+
+        Self::extend_record(&env, &key);
+        Ok(())
+    }
+
+    /// Extends the TTL of what the employer's `operators` have moved under
+    /// their delegations, of their claim approver grants, and of the
+    /// employer's payroll approvers. Anyone may call this.
+    pub fn bump_operators(env: Env, employer: Address, operators: Vec<Address>) -> Result<(), LifecycleError> {
+        Self::ensure_initialized(&env)?;
+
+        for operator in operators.iter() {
+            Self::extend_record(&env, &DataKey::OperatorUsage(employer.clone(), operator.clone()));
+            Self::extend_record(&env, &DataKey::ClaimApprover(employer.clone(), operator));
+        }
+        Self::extend_record(&env, &DataKey::PayrollApprovers(employer));
+        Ok(())
+    }
+
+    /// One-off migration for deployments that predate per-record storage.
+    /// Walks the next `limit` contract ids (at most `MAX_PAGE_SCAN`) and
+    /// converts each legacy record found in instance storage into a
+    /// persistent `Contract`, together with its parties' contract lists.
+    /// Picks up where the previous call stopped. Returns how many contracts
+    /// were moved. Moved contracts have no vault reservation yet; fund them
+    /// through `reserve_contract_funds` before paying them.
+    pub fn migrate_storage(env: Env, admin: Address, limit: u32) -> Result<u32, LifecycleError> {
+        admin.require_auth();
+        Self::ensure_initialized(&env)?;
+        if admin != Self::get_admin(&env)? { return Err(LifecycleError::NotAuthorized); }
+
+        let mut cursor: u32 = env.storage().instance().get(&DataKey::MigrationCursor).unwrap_or(1);
+        let end = Self::get_next_id(&env).min(cursor.saturating_add(limit.min(MAX_PAGE_SCAN)));
+
+        let mut migrated = 0;
+        while cursor < end {
+            if Self::migrate_contract(&env, cursor) { migrated += 1; }
+            cursor += 1;
+        }
+        env.storage().instance().set(&DataKey::MigrationCursor, &cursor);
+
+        Ok(migrated)
+    }
+
+    /// Tops a contract's vault reservation up to what it still owes, for
+    /// contracts that predate reservations and so cannot be paid from one.
+    /// The admin or the employer may call it. Returns the amount reserved,
+    /// which is zero when the reservation already covers the contract.
+    pub fn reserve_contract_funds(env: Env, caller: Address, contract_id: u32) -> Result<i128, LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(&env)?;
+
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        if caller != contract.employer && caller != Self::get_admin(&env)? { return Err(LifecycleError::NotAuthorized); }
+        if matches!(contract.status, ContractStatus::Completed | ContractStatus::Cancelled | ContractStatus::Expired) {
+            return Err(LifecycleError::ContractNotActive);
+        }
+
+        // An outstanding advance has already left the reservation; a keeper
+        // pool is held in it on top of the contract's own funds.
+        let keeper_pool = Self::get_keeper_terms(env.clone(), contract_id).map(|terms| terms.reserved).unwrap_or(0);
+        let owed = contract.total_amount - contract.paid_amount
+            - Self::get_outstanding_advance(env.clone(), contract_id)
+            + keeper_pool;
+        let unspent = Self::vault_client(&env)?
+            .get_reservation(&contract_id)
+            .map(|reservation| reservation.reserved - reservation.spent)
+            .unwrap_or(0);
+
+        let shortfall = owed - unspent;
+        if shortfall <= 0 { return Ok(0); }
+
+        Self::reserve_funds(&env, contract_id, &contract.asset, shortfall)?;
+        env.events().publish((symbol_short!("RESERVED"), contract_id), (caller, shortfall));
+        Ok(shortfall)
+    }
+
+    // ====================================================================
+    // Upgrades
+    // ====================================================================
+
+    /// Replaces the lifecycle's code, keeping its storage. Run `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        upgrade_wasm(&env, &Self::get_admin(&env)?, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Stamps storage with schema version 1 once `migrate_storage` has
+    /// converted every pre-versioning contract; fails with `MigrationPending`
+    /// until then.
+    pub fn migrate(env: Env) -> Result<u32, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let from = pending_migration(&env, &Self::get_admin(&env)?, SCHEMA_VERSION)
+            .ok_or(LifecycleError::AlreadyMigrated)?;
+
+        let cursor: u32 = env.storage().instance().get(&DataKey::MigrationCursor).unwrap_or(1);
+        if from == 0 && cursor < Self::get_next_id(&env) { return Err(LifecycleError::MigrationPending); }
+
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
+
+    // ====================================================================
+    // Getters
+    // ====================================================================
+
+    pub fn get_contract(env: Env, id: u32) -> Result<Contract, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        Self::get_contract_internal(&env, id)
+    }
+
+    pub fn get_payment_schedule(env: Env, contract_id: u32) -> Option<PaymentSchedule> {
+        env.storage().persistent().get(&DataKey::Schedule(contract_id))
+    }
+
+    pub fn get_stream(env: Env, contract_id: u32) -> Option<Stream> {
+        env.storage().persistent().get(&DataKey::Stream(contract_id))
+    }
+
+    /// Amount the employee could withdraw from a stream right now.
+    pub fn get_streamed_balance(env: Env, contract_id: u32) -> Result<i128, LifecycleError> {
+        Self::ensure_initialized(&env)?;
+        let contract = Self::get_contract_internal(&env, contract_id)?;
+        Self::streamed_balance(&env, &contract)
+    }
+
+    pub fn get_employer_contracts(env: Env, employer: Address) -> Vec<u32> {
+        if !Self::is_initialized(&env) { return Vec::new(&env); }
+        env.storage().persistent().get(&DataKey::EmployerContracts(employer)).unwrap_or(Vec::new(&env))
+    }
+
+    pub fn get_employee_contracts(env: Env, employee: Address) -> Vec<u32> {
+        if !Self::is_initialized(&env) { return Vec::new(&env); }
+        env.storage().persistent().get(&DataKey::EmployeeContracts(employee)).unwrap_or(Vec::new(&env))
+    }
+
+    /// Full records of an employer's contracts matching `filter`, starting at
+    /// position `cursor` in the employer's list. A page may hold fewer than
+    /// `limit` records when the filter is sparse; keep following `next_cursor`.
+    pub fn query_employer_contracts(
+        env: Env,
+        employer: Address,
+        filter: ContractFilter,
+        cursor: u32,
+        limit: u32,
+    ) -> ContractPage {
+        let ids = Self::get_employer_contracts(env.clone(), employer);
+        Self::page_contracts(&env, &ids, &filter, cursor, limit)
+    }
+
+    /// Employee-side counterpart of `query_employer_contracts`.
+    pub fn query_employee_contracts(
+        env: Env,
+        employee: Address,
+        filter: ContractFilter,
+        cursor: u32,
+        limit: u32,
+    ) -> ContractPage {
+        let ids = Self::get_employee_contracts(env.clone(), employee);
+        Self::page_contracts(&env, &ids, &filter, cursor, limit)
+    }
+
+    /// Payment records of a contract in the order they were made, starting at
+    /// sequence number `cursor`. At most `MAX_PAGE_SIZE` records per call.
+    pub fn get_payment_history(env: Env, contract_id: u32, cursor: u32, limit: u32) -> Vec<PaymentRecord> {
+        let end = Self::get_payment_count(env.clone(), contract_id).min(cursor.saturating_add(limit.min(MAX_PAGE_SIZE)));
+        let mut records = Vec::new(&env);
+        for seq in cursor..end {
+            if let Some(record) = env.storage().persistent().get(&DataKey::Payment(contract_id, seq)) {
+                records.push_back(record);
+            }
+        }
+        records
+    }
+
+    pub fn get_payment_count(env: Env, contract_id: u32) -> u32 {
+        env.storage().persistent().get(&DataKey::PaymentCount(contract_id)).unwrap_or(0)
+    }
+
+    /// Value an operator has moved for `employer` under their current
+    /// delegation, as counted against its cap.
+    pub fn get_operator_usage(env: Env, employer: Address, operator: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get::<_, (u64, i128)>(&DataKey::OperatorUsage(employer, operator))
+            .map(|(_, used)| used)
+            .unwrap_or(0)
+    }
+
+    pub fn get_admin(env: &Env) -> Result<Address, LifecycleError> {
+        env.storage().instance().get(&DataKey::Admin).ok_or(LifecycleError::NotInitialized)
+    }
+
+    pub fn is_initialized(env: &Env) -> bool {
+        env.storage().instance().has(&DataKey::Initialized)
+    }
+
+    // ====================================================================
+    // Internal Helpers
+    // ====================================================================
+
+    fn ensure_initialized(env: &Env) -> Result<(), LifecycleError> {
+        if !Self::is_initialized(env) { return Err(LifecycleError::NotInitialized); }
+        env.storage().instance().extend_ttl(INSTANCE_BUMP_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        Ok(())
+    }
+
+    /// Writes a per-record persistent entry and extends its TTL.
+    fn persist<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, RECORD_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT);
+    }
+
+    fn extend_record(env: &Env, key: &DataKey) {
+        if env.storage().persistent().has(key) {
+            env.storage().persistent().extend_ttl(key, RECORD_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT);
+        }
+    }
+
+    /// Converts a legacy contract record, if there is one for `contract_id`,
+    /// into a persistent `Contract`. Legacy contracts went live when they
+    /// were created and have no term, so they count as accepted at creation.
+    fn migrate_contract(env: &Env, contract_id: u32) -> bool {
+        let key = DataKey::Contract(contract_id);
+        let legacy: Option<LegacyContract> = env.storage().instance().get(&key);
+        let Some(legacy) = legacy else { return false; };
+
+        let contract = Contract {
+            id: legacy.id,
+            employer: legacy.employer,
+            employee: legacy.employee,
+            contract_type: legacy.contract_type,
+            status: legacy.status,
+            total_amount: legacy.total_amount,
+            paid_amount: legacy.paid_amount,
+            asset: legacy.asset,
+            metadata: legacy.metadata,
+            created_at: legacy.created_at,
+            milestones: legacy.milestones,
+            offer_expires_at: legacy.created_at,
+            accepted_at: Some(legacy.created_at),
+            version: 1,
+            start_date: legacy.created_at,
+            end_date: None,
+        };
+        Self::persist(env, &key, &contract);
+        env.storage().instance().remove(&key);
+
+        Self::migrate_list(env, &DataKey::EmployerContracts(contract.employer));
+        Self::migrate_list(env, &DataKey::EmployeeContracts(contract.employee));
+        true
+    }
+
+    /// Merges a legacy contract-id list into its persistent counterpart, which
+    /// may already hold contracts created after the upgrade.
+    fn migrate_list(env: &Env, key: &DataKey) {
+        let legacy: Option<Vec<u32>> = env.storage().instance().get(key);
+        let Some(legacy) = legacy else { return; };
+
+        let mut contracts: Vec<u32> = env.storage().persistent().get(key).unwrap_or(Vec::new(env));
+        for (position, contract_id) in legacy.iter().enumerate() {
+            if !contracts.contains(contract_id) { contracts.insert(position as u32, contract_id); }
+        }
+        Self::persist(env, key, &contracts);
+        env.storage().instance().remove(key);
+    }
+
+    fn get_next_id(env: &Env) -> u32 {
+        env.storage().instance().get(&DataKey::NextContractId).unwrap_or(1)
+    }
+
+    fn get_contract_internal(env: &Env, id: u32) -> Result<Contract, LifecycleError> {
+        env.storage().persistent().get(&DataKey::Contract(id)).ok_or(LifecycleError::ContractNotFound)
+    }
+
+    fn add_to_employer_list(env: &Env, employer: Address, contract_id: u32) {
+        let key = DataKey::EmployerContracts(employer);
+        let mut contracts: Vec<u32> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        contracts.push_back(contract_id);
+        Self::persist(env, &key, &contracts);
+    }
+
+    fn add_to_employee_list(env: &Env, employee: Address, contract_id: u32) {
+        let key = DataKey::EmployeeContracts(employee);
+        let mut contracts: Vec<u32> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        contracts.push_back(contract_id);
+        Self::persist(env, &key, &contracts);
+    }
+
+    fn store_new_contract(
+        env: &Env,
+        employer: Address,
+        employee: Address,
+        contract_type: ContractType,
+        total_amount: i128,
+        asset: Address,
+        metadata: ContractMetadata,
+        milestones: Option<Vec<Milestone>>,
+        offer_expires_at: u64,
+        start_date: u64,
+        end_date: Option<u64>,
+    ) -> Result<u32, LifecycleError> {
+        let contract_id = Self::get_next_id(env);
+        Self::reserve_funds(env, contract_id, &asset, total_amount)?;
+
+        let contract = Contract {
+            id: contract_id,
+            employer: employer.clone(),
+            employee: employee.clone(),
+            contract_type: contract_type.clone(),
+            status: ContractStatus::Draft,
+            total_amount,
+            paid_amount: 0,
+            asset,
+            metadata,
+            created_at: env.ledger().timestamp(),
+            milestones,
+            offer_expires_at,
+            accepted_at: None,
+            version: 1,
+            start_date,
+            end_date,
+        };
+
+        Self::persist(env, &DataKey::Contract(contract_id), &contract);
+        Self::add_to_employer_list(env, employer.clone(), contract_id);
+        Self::add_to_employee_list(env, employee.clone(), contract_id);
+        env.storage().instance().set(&DataKey::NextContractId, &(contract_id + 1));
+
+        env.events().publish(
+            (symbol_short!("CREATE"), contract_id),
+            (employer, employee, contract_type, offer_expires_at),
+        );
+
+        Ok(contract_id)
+    }
+
+    fn apply_changes(
+        env: &Env,
+        mut contract: Contract,
+        changes: &AmendmentChanges,
+    ) -> Result<Contract, LifecycleError> {
+        if changes.total_amount.is_none()
+            && changes.asset.is_none()
+            && changes.add_milestones.is_none()
+            && changes.end_date.is_none()
+        {
+            return Err(LifecycleError::InvalidAmendment);
+        }
+
+        if let Some(end_date) = changes.end_date {
+            if end_date <= env.ledger().timestamp() || end_date <= contract.start_date {
+                return Err(LifecycleError::InvalidSchedule);
+            }
+            contract.end_date = Some(end_date);
+        }
+
+        if let Some(total_amount) = changes.total_amount {
+            let committed = contract.paid_amount + Self::get_outstanding_advance(env.clone(), contract.id);
+            if total_amount <= 0 || total_amount < committed { return Err(LifecycleError::InvalidAmendment); }
+            contract.total_amount = total_amount;
+            contract.metadata.amount = total_amount;
+        }
+
+        if changes.total_amount.is_some() || changes.end_date.is_some() {
+            if let Some(schedule) = Self::amended_schedule(env, &contract) {
+                Self::validate_schedule(&schedule, contract.total_amount)?;
+            }
+        }
+
+        if let Some(asset) = changes.asset.clone() {
+            Self::ensure_employee_can_receive_payment(env, &contract.employee, &asset)?;
+            contract.metadata.asset = asset.clone();
+            contract.asset = asset;
+        }
+
+        if let Some(new_milestones) = changes.add_milestones.clone() {
+            if contract.contract_type != ContractType::Milestone { return Err(LifecycleError::InvalidContractType); }
+            let mut milestones = contract.milestones.unwrap_or(Vec::new(env));
+            for mut milestone in new_milestones.iter() {
+                milestone.completed = false;
+                milestone.completed_at = None;
+                milestones.push_back(milestone);
+            }
+            contract.milestones = Some(milestones);
+        }
+
+        if changes.total_amount.is_some() || changes.add_milestones.is_some() {
+            if let Some(milestones) = contract.milestones.as_ref() {
+                Self::validate_milestones(milestones, contract.total_amount)?;
+            }
+        }
+
+        Ok(contract)
+    }
+
+    /// Milestone ids must be unique and amounts positive, and together the
+    /// milestones may not promise more than the contract's total.
+    fn validate_milestones(milestones: &Vec<Milestone>, total_amount: i128) -> Result<(), LifecycleError> {
+        let mut sum: i128 = 0;
+        for (index, milestone) in milestones.iter().enumerate() {
+            if milestone.amount <= 0 || milestones.iter().skip(index + 1).any(|m| m.id == milestone.id) {
+                return Err(LifecycleError::InvalidMilestoneData);
+            }
+            sum += milestone.amount;
+        }
+        if sum > total_amount { return Err(LifecycleError::InvalidMilestoneData); }
+        Ok(())
+    }
+
+    /// The contract's payment schedule, ending at its (amended) end date.
+    fn amended_schedule(env: &Env, contract: &Contract) -> Option<PaymentSchedule> {
+        let mut schedule: PaymentSchedule = env.storage().persistent().get(&DataKey::Schedule(contract.id))?;
+        if let Some(end_date) = contract.end_date { schedule.end_time = end_date; }
+        Some(schedule)
+    }
+
+    /// Carries an approved amendment over to the contract's time windows. A
+    /// renewal moves the end of the schedule; a stream whose term or total
+    /// changed keeps what it has accrued and vests the rest from now on.
+    fn amend_windows(env: &Env, before: &Contract, after: &Contract) -> Result<(), LifecycleError> {
+        if before.end_date == after.end_date && before.total_amount == after.total_amount { return Ok(()); }
+
+        if before.end_date != after.end_date {
+            if let Some(schedule) = Self::amended_schedule(env, after) {
+                Self::persist(env, &DataKey::Schedule(after.id), &schedule);
+            }
+        }
+
+        let stream: Option<Stream> = env.storage().persistent().get(&DataKey::Stream(after.id));
+        let Some(mut stream) = stream else { return Ok(()); };
+        let now = env.ledger().timestamp();
+        if now > stream.start_time {
+            stream.accrued_before = Self::accrued_until(env, before, now)?.min(after.total_amount);
+            stream.start_time = now;
+        }
+        if let Some(end_date) = after.end_date { stream.end_time = end_date; }
+        Self::persist(env, &DataKey::Stream(after.id), &stream);
+        Ok(())
+    }
+
+    fn find_milestone(contract: &Contract, milestone_id: u32) -> Result<Milestone, LifecycleError> {
+        let milestones = contract.milestones.as_ref().ok_or(LifecycleError::InvalidMilestoneData)?;
+        milestones.iter().find(|m| m.id == milestone_id).ok_or(LifecycleError::MilestoneNotFound)
+    }
+
+    fn mark_milestone_completed(env: &Env, contract: &mut Contract, milestone_id: u32) -> Result<(), LifecycleError> {
+        let mut milestones = contract.milestones.clone().ok_or(LifecycleError::InvalidMilestoneData)?;
+        let mut found = false;
+
+        for i in 0..milestones.len() {
+            let mut milestone = milestones.get(i).unwrap();
+            if milestone.id == milestone_id {
+                if milestone.completed { return Err(LifecycleError::MilestoneAlreadyCompleted); }
+                milestone.completed = true;
+                milestone.completed_at = Some(env.ledger().timestamp());
+                milestones.set(i, milestone);
+                found = true;
+                break;
+            }
+        }
+
+        if !found { return Err(LifecycleError::MilestoneNotFound); }
+
+        contract.milestones = Some(milestones);
+        Ok(())
+    }
+
+    fn is_milestone_paid(env: &Env, contract_id: u32, milestone_id: u32) -> bool {
+        Self::get_paid_milestones(env.clone(), contract_id).contains(milestone_id)
+    }
+
+    fn mark_milestone_paid(env: &Env, contract_id: u32, milestone_id: u32) {
+        let mut paid = Self::get_paid_milestones(env.clone(), contract_id);
+        paid.push_back(milestone_id);
+        Self::persist(env, &DataKey::PaidMilestones(contract_id), &paid);
+    }
+
+    fn has_pending_submission(env: &Env, contract_id: u32, milestone_id: u32) -> bool {
+        let history: Vec<MilestoneReview> = env.storage()
+            .persistent()
+            .get(&DataKey::MilestoneHistory(contract_id, milestone_id))
+            .unwrap_or(Vec::new(env));
+        matches!(history.last(), Some(entry) if entry.action == ReviewAction::Submitted)
+    }
+
+    fn record_milestone_review(
+        env: &Env,
+        contract_id: u32,
+        milestone_id: u32,
+        action: ReviewAction,
+        actor: &Address,
+        note: String,
+    ) {
+        let key = DataKey::MilestoneHistory(contract_id, milestone_id);
+        let mut history: Vec<MilestoneReview> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        history.push_back(MilestoneReview {
+            action,
+            actor: actor.clone(),
+            note,
+            timestamp: env.ledger().timestamp(),
+        });
+        Self::persist(env, &key, &history);
+    }
+
+    fn page_contracts(env: &Env, ids: &Vec<u32>, filter: &ContractFilter, cursor: u32, limit: u32) -> ContractPage {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let scan_end = ids.len().min(cursor.saturating_add(MAX_PAGE_SCAN));
+        let mut contracts = Vec::new(env);
+        let mut position = cursor;
+
+        while position < scan_end && contracts.len() < limit {
+            let id = ids.get_unchecked(position);
+            position += 1;
+
+            let Ok(contract) = Self::get_contract_internal(env, id) else { continue; };
+            if !filter.statuses.is_empty() && !filter.statuses.contains(&contract.status) { continue; }
+            if !filter.contract_types.is_empty() && !filter.contract_types.contains(&contract.contract_type) { continue; }
+            if !filter.assets.is_empty() && !filter.assets.contains(&contract.asset) { continue; }
+            contracts.push_back(contract);
+        }
+
+        let next_cursor = if position < ids.len() { Some(position) } else { None };
+        ContractPage { contracts, next_cursor }
+    }
+
+    fn is_metered(env: &Env, contract_id: u32) -> bool {
+        env.storage().persistent().has(&DataKey::HourlyRate(contract_id))
+            || env.storage().persistent().has(&DataKey::UsageTerms(contract_id))
+    }
+
+    fn review_timesheet(
+        env: &Env,
+        employer: &Address,
+        contract_id: u32,
+        period: u32,
+        outcome: TimesheetStatus,
+    ) -> Result<(), LifecycleError> {
+        employer.require_auth();
+        Self::ensure_initialized(env)?;
+
+        let contract = Self::get_contract_internal(env, contract_id)?;
+        if contract.employer != *employer { return Err(LifecycleError::NotAuthorized); }
+
+        let key = DataKey::Timesheet(contract_id, period);
+        let mut timesheet: Timesheet = env.storage().persistent().get(&key).ok_or(LifecycleError::TimesheetNotFound)?;
+        if timesheet.status != TimesheetStatus::Submitted { return Err(LifecycleError::InvalidTimesheetState); }
+
+        timesheet.status = outcome;
+        timesheet.reviewed_at = Some(env.ledger().timestamp());
+        Self::persist(env, &key, &timesheet);
+        Ok(())
+    }
+
+    /// Moves a submitted claim to `outcome` on behalf of the employer or one
+    /// of their claim approvers. The caller persists the returned claim.
+    fn review_claim(
+        env: &Env,
+        caller: &Address,
+        contract_id: u32,
+        claim_id: u32,
+        outcome: ClaimStatus,
+    ) -> Result<(Contract, ExpenseClaim), LifecycleError> {
+        caller.require_auth();
+        Self::ensure_initialized(env)?;
+
+        let contract = Self::get_contract_internal(env, contract_id)?;
+        if contract.employer != *caller && !Self::is_claim_approver(env.clone(), contract.employer.clone(), caller.clone()) {
+            return Err(LifecycleError::NotAuthorized);
+        }
+
+        let mut claim = Self::get_claim(env.clone(), contract_id, claim_id).ok_or(LifecycleError::ClaimNotFound)?;
+        if claim.status != ClaimStatus::Submitted { return Err(LifecycleError::InvalidClaimState); }
+
+        claim.status = outcome.clone();
+        claim.history.push_back(ClaimStatusChange {
+            status: outcome,
+            actor: caller.clone(),
+            timestamp: env.ledger().timestamp(),
+        });
+        Ok((contract, claim))
+    }
+
+    fn period_length(cadence: &PaymentCadence) -> u64 {
+        match cadence {
+            PaymentCadence::Weekly => 7 * DAY_IN_SECONDS,
+            PaymentCadence::Biweekly => 14 * DAY_IN_SECONDS,
+            PaymentCadence::Monthly => 30 * DAY_IN_SECONDS,
+        }
+    }
+
+    fn total_periods(schedule: &PaymentSchedule) -> u32 {
+        ((schedule.end_time - schedule.start_time) / Self::period_length(&schedule.cadence)) as u32
+    }
+
+    fn periods_due(schedule: &PaymentSchedule, now: u64) -> u32 {
+        if now <= schedule.start_time { return 0; }
+        let elapsed = (now - schedule.start_time) / Self::period_length(&schedule.cadence);
+        let total = Self::total_periods(schedule);
+        if elapsed > total as u64 { total } else { elapsed as u32 }
+    }
+
+    fn validate_schedule(schedule: &PaymentSchedule, total_amount: i128) -> Result<(), LifecycleError> {
+        if schedule.amount_per_period <= 0 || schedule.end_time <= schedule.start_time {
+            return Err(LifecycleError::InvalidSchedule);
+        }
+
+        let periods = Self::total_periods(schedule);
+        if periods == 0 { return Err(LifecycleError::InvalidSchedule); }
+        if schedule.amount_per_period * periods as i128 > total_amount {
+            return Err(LifecycleError::InsufficientContractFunds);
+        }
+
+        Ok(())
+    }
+
+    fn streamed_balance(env: &Env, contract: &Contract) -> Result<i128, LifecycleError> {
+        if contract.contract_type != ContractType::Streaming { return Err(LifecycleError::InvalidContractType); }
+        let accrued = Self::accrued_until(env, contract, env.ledger().timestamp())?;
+        Ok(accrued - contract.paid_amount)
+    }
 
-        let remaining = contract.total_amount - contract.paid_amount;
-        if amount > remaining { return Err(LifecycleError::InsufficientContractFunds); }
+    /// Pro-rata amount earned by `at` on time-based contracts: the linear
+    /// accrual of a stream, or a FixedRate schedule including partial periods.
+    /// Other contracts have no time-based accrual.
+    fn accrued_until(env: &Env, contract: &Contract, at: u64) -> Result<i128, LifecycleError> {
+        match contract.contract_type {
+            ContractType::Streaming => {
+                let stream: Stream = env.storage()
+                    .persistent()
+                    .get(&DataKey::Stream(contract.id))
+                    .ok_or(LifecycleError::InvalidSchedule)?;
+                if at <= stream.start_time || stream.end_time <= stream.start_time { return Ok(stream.accrued_before); }
+
+                let elapsed = at.min(stream.end_time) - stream.start_time;
+                let duration = stream.end_time - stream.start_time;
+                let vesting = contract.total_amount - stream.accrued_before;
+                Ok(stream.accrued_before + vesting * elapsed as i128 / duration as i128)
+            }
+            ContractType::FixedRate => {
+                let schedule: Option<PaymentSchedule> = env.storage().persistent().get(&DataKey::Schedule(contract.id));
+                let Some(schedule) = schedule else { return Ok(0); };
+                if at <= schedule.start_time { return Ok(0); }
+
+                let elapsed = at.min(schedule.end_time) - schedule.start_time;
+                let period = Self::period_length(&schedule.cadence);
+                Ok(schedule.amount_per_period * elapsed as i128 / period as i128)
+            }
+            _ => Ok(0),
+        }
+    }
 
-        Self::ensure_employee_can_receive_payment(&env, &contract.employee, &contract.asset)?;
-        Self::process_vault_payment(&env, &contract.employee, amount, &contract.asset)?;
+    /// Clears an active suspension and shifts the contract's time windows by
+    /// the time spent on hold. Returns that duration; zero if not suspended.
+    fn lift_suspension(env: &Env, contract: &mut Contract) -> Result<u64, LifecycleError> {
+        let key = DataKey::Suspension(contract.id);
+        let suspension: Option<Suspension> = env.storage().persistent().get(&key);
+        let Some(suspension) = suspension else { return Ok(0); };
+        let now = env.ledger().timestamp();
+        let held_for = now - suspension.suspended_at;
+        // Only the part of the hold that fell after a window opened delays it.
+        let held_since = |start: u64| now.saturating_sub(suspension.suspended_at.max(start));
+
+        let schedule: Option<PaymentSchedule> = env.storage().persistent().get(&DataKey::Schedule(contract.id));
+        if let Some(mut schedule) = schedule {
+            let shift = held_since(schedule.start_time);
+            schedule.start_time += shift;
+            schedule.end_time += shift;
+            Self::persist(env, &DataKey::Schedule(contract.id), &schedule);
+        }
 
-        contract.paid_amount += amount;
-        if contract.paid_amount >= contract.total_amount {
-            contract.status = ContractStatus::Completed;
+        let stream: Option<Stream> = env.storage().persistent().get(&DataKey::Stream(contract.id));
+        if let Some(mut stream) = stream {
+            let shift = held_since(stream.start_time);
+            stream.start_time += shift;
+            stream.end_time += shift;
+            Self::persist(env, &DataKey::Stream(contract.id), &stream);
+        }
+
+        contract.end_date = contract.end_date.map(|end_date| end_date + held_since(contract.start_date));
+        env.storage().persistent().remove(&key);
+        Ok(held_for)
+    }
+
+    fn is_expired(env: &Env, contract: &Contract) -> bool {
+        let now = env.ledger().timestamp();
+        match contract.status {
+            ContractStatus::Active => matches!(contract.end_date, Some(end_date) if now >= end_date),
+            ContractStatus::Draft => now > contract.offer_expires_at,
+            _ => false,
+        }
+    }
+
+    fn expire(env: &Env, contract: &mut Contract) -> Result<(), LifecycleError> {
+        // An offer that lapsed was never accepted, so nothing is owed on it.
+        if contract.status == ContractStatus::Draft {
+            Self::release_funds(env, contract.id, contract.total_amount - contract.paid_amount)?;
+            Self::release_keeper_pool(env, contract.id)?;
+
+            contract.status = ContractStatus::Expired;
+            Self::persist(env, &DataKey::Contract(contract.id), contract);
+
+            env.events().publish((symbol_short!("OFFER_EXP"), contract.id), contract.offer_expires_at);
+            return Ok(());
+        }
+
+        let end_date = contract.end_date.unwrap_or(env.ledger().timestamp());
+        Self::settle_termination(env, contract, end_date, 0)?;
+
+        contract.status = ContractStatus::Expired;
+        Self::persist(env, &DataKey::Contract(contract.id), contract);
+
+        env.events().publish((symbol_short!("EXPIRED"), contract.id), end_date);
+        Ok(())
+    }
+
+    /// Pays the employee what they earned up to `effective_at`, including
+    /// approved work that was never paid, plus `severance`, capped at what the
+    /// contract still owes, and releases the rest of the reservation back to
+    /// the employer.
+    fn settle_termination(
+        env: &Env,
+        contract: &mut Contract,
+        effective_at: u64,
+        severance: i128,
+    ) -> Result<Settlement, LifecycleError> {
+        let remaining = contract.total_amount - contract.paid_amount;
+        let accrued = (Self::accrued_until(env, contract, effective_at)? - contract.paid_amount).max(0);
+        let unpaid = Self::unpaid_work(env, contract);
+        let earned = (accrued + unpaid.amount).min(remaining);
+        let severance = severance.min(remaining - earned);
+        let payout = earned + severance;
+
+        if payout > 0 {
+            Self::pay_employee(env, contract, payout, PaymentKind::Settlement, None, None)?;
+            contract.paid_amount += payout;
         }
+        Self::mark_work_paid(env, contract.id, &unpaid);
+
+        // The employer absorbs whatever part of an advance the settlement
+        // could not recover; that money has already left the reservation.
+        let written_off = Self::write_off_advance(env, contract.id);
+        let settlement = Settlement { earned, severance, employer_refund: remaining - payout - written_off };
+        Self::release_funds(env, contract.id, settlement.employer_refund)?;
+        Self::release_keeper_pool(env, contract.id)?;
 
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
         env.events().publish(
-            (symbol_short!("PAYG"), contract_id),
-            (employer, contract.employee, amount),
+            (symbol_short!("SETTLED"), contract.id),
+            settlement.clone(),
         );
 
-        Ok(())
+        Ok(settlement)
     }
 
-    pub fn cancel_contract(
-        env: Env,
-        caller: Address,
-        contract_id: u32,
-    ) -> Result<(), LifecycleError> {
-        caller.require_auth();
-        Self::ensure_initialized(&env)?;
+    /// Approved work on the contract that has not been paid: completed
+    /// milestones, approved timesheets and usage reports whose challenge
+    /// window has passed unchallenged.
+    fn unpaid_work(env: &Env, contract: &Contract) -> UnpaidWork {
+        let mut work = UnpaidWork {
+            milestones: Vec::new(env),
+            timesheets: Vec::new(env),
+            usage_reports: Vec::new(env),
+            amount: 0,
+        };
 
-        let mut contract = Self::get_contract_internal(&env, contract_id)?;
-        if contract.employer != caller {
-            let admin = Self::get_admin(&env)?;
-            if caller != admin { return Err(LifecycleError::NotAuthorized); }
+        for milestone in contract.milestones.clone().unwrap_or(Vec::new(env)).iter() {
+            if milestone.completed && !Self::is_milestone_paid(env, contract.id, milestone.id) {
+                work.milestones.push_back(milestone.id);
+                work.amount += milestone.amount;
+            }
         }
 
-        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+        let rate = Self::get_hourly_rate(env.clone(), contract.id).unwrap_or(0);
+        for period in Self::open_timesheets(env, contract.id).iter() {
+            let Some(timesheet) = Self::get_timesheet(env.clone(), contract.id, period) else { continue; };
+            if timesheet.status == TimesheetStatus::Approved {
+                work.timesheets.push_back(period);
+                work.amount += rate * timesheet.hours as i128;
+            }
+        }
 
-        contract.status = ContractStatus::Cancelled;
-        env.storage().instance().set(&DataKey::Contract(contract_id), &contract);
+        if let Some(terms) = Self::get_usage_terms(env.clone(), contract.id) {
+            let now = env.ledger().timestamp();
+            for period in Self::open_usage_reports(env, contract.id).iter() {
+                let Some(report) = Self::get_usage_report(env.clone(), contract.id, period) else { continue; };
+                if report.status == UsageStatus::Pending && now >= report.reported_at + terms.challenge_window {
+                    work.usage_reports.push_back(period);
+                    work.amount += terms.unit_price * report.units as i128;
+                }
+            }
+        }
 
-        env.events().publish((symbol_short!("CANCEL"), contract_id), caller);
-        Ok(())
+        work
     }
 
-    pub fn get_contract(env: Env, id: u32) -> Result<Contract, LifecycleError> {
-        Self::ensure_initialized(&env)?;
-        Self::get_contract_internal(&env, id)
+    /// Marks the work a settlement paid for as paid.
+    fn mark_work_paid(env: &Env, contract_id: u32, work: &UnpaidWork) {
+        for milestone_id in work.milestones.iter() {
+            Self::mark_milestone_paid(env, contract_id, milestone_id);
+        }
+        for period in work.timesheets.iter() {
+            let Some(mut timesheet) = Self::get_timesheet(env.clone(), contract_id, period) else { continue; };
+            Self::mark_timesheet_paid(env, contract_id, &mut timesheet);
+        }
+        for period in work.usage_reports.iter() {
+            let Some(mut report) = Self::get_usage_report(env.clone(), contract_id, period) else { continue; };
+            Self::mark_usage_paid(env, contract_id, &mut report);
+        }
     }
 
-    pub fn get_employer_contracts(env: Env, employer: Address) -> Vec<u32> {
-        if !Self::is_initialized(&env) { return Vec::new(&env); }
-        env.storage().instance().get(&DataKey::EmployerContracts(employer)).unwrap_or(Vec::new(&env))
+    /// Periods of the contract with a timesheet that has not been paid yet.
+    fn open_timesheets(env: &Env, contract_id: u32) -> Vec<u32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OpenTimesheets(contract_id))
+            .unwrap_or(Vec::new(env))
     }
 
-    pub fn get_employee_contracts(env: Env, employee: Address) -> Vec<u32> {
-        if !Self::is_initialized(&env) { return Vec::new(&env); }
-        env.storage().instance().get(&DataKey::EmployeeContracts(employee)).unwrap_or(Vec::new(&env))
+    /// Periods of the contract with a usage report that has not been paid yet.
+    fn open_usage_reports(env: &Env, contract_id: u32) -> Vec<u32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OpenUsageReports(contract_id))
+            .unwrap_or(Vec::new(env))
     }
 
-    pub fn get_admin(env: &Env) -> Result<Address, LifecycleError> {
-        env.storage().instance().get(&DataKey::Admin).ok_or(LifecycleError::NotInitialized)
+    fn mark_usage_paid(env: &Env, contract_id: u32, report: &mut UsageReport) {
+        report.status = UsageStatus::Paid;
+        Self::persist(env, &DataKey::UsageReport(contract_id, report.period), report);
+
+        let mut open = Self::open_usage_reports(env, contract_id);
+        if let Some(index) = open.first_index_of(report.period) {
+            open.remove(index);
+            Self::persist(env, &DataKey::OpenUsageReports(contract_id), &open);
+        }
     }
 
-    pub fn is_initialized(env: &Env) -> bool {
-        env.storage().instance().has(&DataKey::Initialized)
+    fn mark_timesheet_paid(env: &Env, contract_id: u32, timesheet: &mut Timesheet) {
+        timesheet.status = TimesheetStatus::Paid;
+        Self::persist(env, &DataKey::Timesheet(contract_id, timesheet.period), timesheet);
+
+        let mut open = Self::open_timesheets(env, contract_id);
+        if let Some(index) = open.first_index_of(timesheet.period) {
+            open.remove(index);
+            Self::persist(env, &DataKey::OpenTimesheets(contract_id), &open);
+        }
     }
 
-    // ====================================================================
-    // Internal Helpers
-    // ====================================================================
+    /// The contract's latest advance, unless it has been settled or rejected.
+    fn open_advance(env: &Env, contract_id: u32) -> Option<Advance> {
+        let count = Self::get_advance_count(env.clone(), contract_id);
+        if count == 0 { return None; }
 
-    fn ensure_initialized(env: &Env) -> Result<(), LifecycleError> {
-        if !Self::is_initialized(env) { return Err(LifecycleError::NotInitialized); }
+        Self::get_advance(env.clone(), contract_id, count - 1)
+            .filter(|advance| matches!(advance.status, AdvanceStatus::Requested | AdvanceStatus::Outstanding))
+    }
+
+    fn pending_advance(env: &Env, contract_id: u32, advance_id: u32) -> Result<Advance, LifecycleError> {
+        match Self::get_advance(env.clone(), contract_id, advance_id) {
+            Some(advance) if advance.status == AdvanceStatus::Requested => Ok(advance),
+            _ => Err(LifecycleError::NoPendingAdvance),
+        }
+    }
+
+    fn ensure_within_advance_limit(contract: &Contract, policy: &AdvancePolicy, amount: i128) -> Result<(), LifecycleError> {
+        let remaining = contract.total_amount - contract.paid_amount;
+        if amount > remaining * policy.max_share_bps as i128 / BASIS_POINTS as i128 {
+            return Err(LifecycleError::AdvanceLimitExceeded);
+        }
         Ok(())
     }
 
-    fn get_next_id(env: &Env) -> u32 {
-        env.storage().instance().get(&DataKey::NextContractId).unwrap_or(1)
+    /// Pays an approved advance out of the contract's reservation like any
+    /// other payout, with deductions withheld. It does not count towards
+    /// `paid_amount` until later payouts recover it, and since it has already
+    /// been taxed, the part of a payout that recovers it is not taxed again.
+    fn disburse_advance(env: &Env, contract: &Contract, advance: &mut Advance) -> Result<(), LifecycleError> {
+        Self::pay_employee(env, contract, advance.amount, PaymentKind::Advance, None, None)?;
+
+        advance.status = AdvanceStatus::Outstanding;
+        advance.outstanding = advance.amount;
+
+        env.events().publish((symbol_short!("ADV_PAID"), contract.id), (advance.id, advance.amount));
+        Ok(())
     }
 
-    fn get_contract_internal(env: &Env, id: u32) -> Result<Contract, LifecycleError> {
-        env.storage().instance().get(&DataKey::Contract(id)).ok_or(LifecycleError::ContractNotFound)
+    /// How much of a payout of `amount` goes towards the outstanding advance.
+    fn advance_recovery(env: &Env, contract_id: u32, amount: i128) -> i128 {
+        match Self::open_advance(env, contract_id) {
+            Some(advance) if advance.status == AdvanceStatus::Outstanding => advance.outstanding.min(amount),
+            _ => 0,
+        }
     }
 
-    fn add_to_employer_list(env: &Env, employer: Address, contract_id: u32) {
-        let key = DataKey::EmployerContracts(employer);
-        let mut contracts: Vec<u32> = env.storage().instance().get(&key).unwrap_or(Vec::new(env));
-        contracts.push_back(contract_id);
-        env.storage().instance().set(&key, &contracts);
+    /// Books `recovered` against the outstanding advance.
+    fn recover_advance(env: &Env, contract_id: u32, recovered: i128) {
+        if recovered == 0 { return; }
+        let Some(mut advance) = Self::open_advance(env, contract_id) else { return; };
+
+        advance.outstanding -= recovered;
+        if advance.outstanding == 0 { advance.status = AdvanceStatus::Repaid; }
+        Self::persist(env, &DataKey::Advance(contract_id, advance.id), &advance);
+
+        env.events().publish((symbol_short!("ADV_REPAY"), contract_id), (advance.id, recovered));
     }
 
-    fn add_to_employee_list(env: &Env, employee: Address, contract_id: u32) {
-        let key = DataKey::EmployeeContracts(employee);
-        let mut contracts: Vec<u32> = env.storage().instance().get(&key).unwrap_or(Vec::new(env));
-        contracts.push_back(contract_id);
-        env.storage().instance().set(&key, &contracts);
+    /// Closes the contract's open advance, writing off what is still
+    /// outstanding, and returns the amount written off.
+    fn write_off_advance(env: &Env, contract_id: u32) -> i128 {
+        let Some(mut advance) = Self::open_advance(env, contract_id) else { return 0; };
+
+        let written_off = advance.outstanding;
+        advance.status = match advance.status {
+            AdvanceStatus::Requested => AdvanceStatus::Rejected,
+            _ => AdvanceStatus::WrittenOff,
+        };
+        advance.outstanding = 0;
+        Self::persist(env, &DataKey::Advance(contract_id, advance.id), &advance);
+
+        if written_off > 0 {
+            env.events().publish((symbol_short!("ADV_WOFF"), contract_id), (advance.id, written_off));
+        }
+        written_off
+    }
+
+    /// Lets `caller` act for `employer` when it is the employer, or an
+    /// operator the employer has delegated `scope` to in the identity
+    /// contract. Only a delegation granted by `employer` itself counts.
+    /// Returns the delegation when an operator is acting.
+    fn authorize_employer(
+        env: &Env,
+        employer: &Address,
+        caller: &Address,
+        scope: OperatorScope,
+    ) -> Result<Option<Delegation>, LifecycleError> {
+        if caller == employer { return Ok(None); }
+
+        let identity_address: Option<Address> = env.storage().instance().get(&DataKey::IdentityAddress);
+        let Some(identity_address) = identity_address else { return Err(LifecycleError::NotAuthorized); };
+
+        let identity_client = IdentityManagementContractClient::new(env, &identity_address);
+        let Ok(Ok(Some(delegation))) = identity_client.try_get_delegation(employer, caller) else {
+            return Err(LifecycleError::NotAuthorized);
+        };
+        if !check_operator(env, &delegation, &scope) { return Err(LifecycleError::NotAuthorized); }
+
+        Ok(Some(delegation))
+    }
+
+    /// Counts `amount` towards the cap of the operator acting for `employer`.
+    /// Usage starts over whenever the employer grants the delegation again.
+    fn charge_operator(
+        env: &Env,
+        employer: &Address,
+        operator: &Address,
+        delegation: Option<Delegation>,
+        amount: i128,
+    ) -> Result<(), LifecycleError> {
+        if let Some(usage) = Self::operator_usage(env, employer, operator, delegation, amount)? {
+            Self::persist(env, &DataKey::OperatorUsage(employer.clone(), operator.clone()), &usage);
+        }
+        Ok(())
+    }
+
+    /// The capped operator's usage once `amount` is counted, without storing
+    /// it. `None` when the caller is not a capped operator.
+    fn operator_usage(
+        env: &Env,
+        employer: &Address,
+        operator: &Address,
+        delegation: Option<Delegation>,
+        amount: i128,
+    ) -> Result<Option<(u64, i128)>, LifecycleError> {
+        let Some(delegation) = delegation else { return Ok(None); };
+        let Some(cap) = delegation.cap else { return Ok(None); };
+
+        let key = DataKey::OperatorUsage(employer.clone(), operator.clone());
+        let used = match env.storage().persistent().get::<_, (u64, i128)>(&key) {
+            Some((granted_at, used)) if granted_at == delegation.granted_at => used,
+            _ => 0,
+        };
+        if used + amount > cap { return Err(LifecycleError::OperatorCapExceeded); }
+
+        Ok(Some((delegation.granted_at, used + amount)))
+    }
+
+    fn is_arbiter(env: &Env, caller: &Address) -> Result<bool, LifecycleError> {
+        if *caller == Self::get_admin(env)? { return Ok(true); }
+
+        let identity_address: Option<Address> = env.storage().instance().get(&DataKey::IdentityAddress);
+        let Some(identity_address) = identity_address else { return Ok(false); };
+
+        let identity_client = IdentityManagementContractClient::new(env, &identity_address);
+        Ok(matches!(identity_client.try_is_manager(caller), Ok(Ok(true))))
     }
 
     fn ensure_employee_can_receive_payment(
@@ -380,24 +3554,450 @@ impl LifecycleContract {
         Ok(())
     }
 
-    fn process_vault_payment(
-        env: &Env,
-        recipient: &Address,
-        amount: i128,
-        asset: &Address,
-    ) -> Result<(), LifecycleError> {
+    fn vault_client(env: &Env) -> Result<VaultContractClient<'_>, LifecycleError> {
         let vault_address: Address = env.storage()
             .instance()
             .get(&DataKey::VaultAddress)
             .ok_or(LifecycleError::VaultContractNotSet)?;
 
-        let vault_client = VaultContractClient::new(env, &vault_address);
-        
+        Ok(VaultContractClient::new(env, &vault_address))
+    }
+
+    /// Sets a contract's funding aside in the vault so it cannot be promised twice.
+    fn reserve_funds(env: &Env, contract_id: u32, asset: &Address, amount: i128) -> Result<(), LifecycleError> {
+        if amount <= 0 { return Ok(()); }
+
+        Self::vault_client(env)?.try_reserve(&contract_id, asset, &amount)
+            .map_err(|_| LifecycleError::InsufficientVaultFunds)?
+            .map_err(|_| LifecycleError::InsufficientVaultFunds)?;
+
+        Ok(())
+    }
+
+    /// Hands an unspent part of a contract's reservation back to the vault.
+    fn release_funds(env: &Env, contract_id: u32, amount: i128) -> Result<(), LifecycleError> {
+        if amount <= 0 { return Ok(()); }
+
+        Self::vault_client(env)?.try_release_reserved(&contract_id, &amount)
+            .map_err(|_| LifecycleError::VaultPaymentFailed)?
+            .map_err(|_| LifecycleError::VaultPaymentFailed)?;
+
+        Ok(())
+    }
+
+    /// Pays out `amount` gross from the contract's reservation. An outstanding
+    /// advance is recovered first; it was taxed when it was paid, so
+    /// deductions are taken from what is left and go to their remittance
+    /// addresses, and the net goes to the employee, or is split between the
+    /// payees of a team contract. The vault withholds its protocol fee from
+    /// the payout, out of the net first, so the payout never spends more than
+    /// the reservation holds for it. The payout is appended to the contract's
+    /// payment history and a payslip event is emitted.
+    fn pay_employee(
+        env: &Env,
+        contract: &Contract,
+        amount: i128,
+        kind: PaymentKind,
+        milestone_id: Option<u32>,
+        memo: Option<String>,
+    ) -> Result<(), LifecycleError> {
+        let payslip = Self::plan_payout(env, contract, amount)?;
+
+        match payslip.payments.len() {
+            0 => {}
+            1 => {
+                let payment = payslip.payments.get_unchecked(0);
+                Self::pay_from_reservation(env, contract.id, &payment.recipient, payment.amount)?;
+            }
+            _ => {
+                Self::vault_client(env)?.try_pay_reserved_split(&contract.id, &payslip.payments)
+                    .map_err(|_| LifecycleError::VaultPaymentFailed)?
+                    .map_err(|_| LifecycleError::VaultPaymentFailed)?;
+            }
+        }
+
+        Self::record_payout(env, contract, amount, &payslip, kind, milestone_id, memo);
+        Ok(())
+    }
+
+    /// Works out who gets what of a payout of `amount` without changing any
+    /// state, so a failing payout leaves nothing behind.
+    fn plan_payout(env: &Env, contract: &Contract, amount: i128) -> Result<Payslip, LifecycleError> {
+        let recovered = Self::advance_recovery(env, contract.id, amount);
+        let payable = amount - recovered;
+
+        let mut payments = Vec::new(env);
+        let mut remittances = Vec::new(env);
+        let mut deducted: i128 = recovered;
+        for deduction in Self::get_deductions(env.clone(), contract.id).iter() {
+            let share = match deduction.rule {
+                SplitRule::Percent(bps) => payable * bps as i128 / BASIS_POINTS as i128,
+                SplitRule::Fixed(share) => share,
+            };
+            if payable == 0 || share <= 0 { continue; }
+
+            deducted += share;
+            payments.push_back(Payment { recipient: deduction.recipient.clone(), amount: share });
+            remittances.push_back((deduction.name, deduction.recipient, share));
+        }
+
+        let mut net = amount - deducted;
+        if net < 0 { return Err(LifecycleError::InvalidSplitRule); }
+
+        let payees = Self::get_payees(env.clone(), contract.id);
+        let net_payments = if net == 0 {
+            Vec::new(env)
+        } else if payees.is_empty() {
+            Vec::from_array(env, [Payment { recipient: contract.employee.clone(), amount: net }])
+        } else {
+            Self::split_payment(env, &payees, net)?
+        };
+        for payment in net_payments.iter() {
+            Self::ensure_employee_can_receive_payment(env, &payment.recipient, &contract.asset)?;
+            if payment.amount > 0 { payments.push_back(payment); }
+        }
+
+        let transferred: i128 = payments.iter().map(|payment| payment.amount).sum();
+        let fee = if transferred > 0 {
+            Self::vault_client(env)?.quote_fee(&contract.asset, &transferred)
+        } else {
+            0
+        };
+
+        // The vault withholds its fee from the last payments first, so it
+        // comes out of the net and only reaches the deductions when the net
+        // cannot cover it.
+        let mut withheld = fee.min(net);
+        net -= withheld;
+        for index in (0..remittances.len()).rev() {
+            if withheld == fee { break; }
+            let (name, recipient, share) = remittances.get_unchecked(index);
+            let cut = (fee - withheld).min(share);
+            withheld += cut;
+            deducted -= cut;
+            remittances.set(index, (name, recipient, share - cut));
+        }
+
+        Ok(Payslip { payments, remittances, recovered, deducted, net, fee })
+    }
+
+    /// Books a payout once its transfers are made or queued: recovers the
+    /// advance, emits the deduction and payslip events and appends the
+    /// payout to the contract's payment history.
+    fn record_payout(
+        env: &Env,
+        contract: &Contract,
+        amount: i128,
+        payslip: &Payslip,
+        kind: PaymentKind,
+        milestone_id: Option<u32>,
+        memo: Option<String>,
+    ) {
+        Self::recover_advance(env, contract.id, payslip.recovered);
+        for (name, recipient, share) in payslip.remittances.iter() {
+            env.events().publish((symbol_short!("DEDUCTED"), contract.id), (name, recipient, share));
+        }
+
+        let seq = Self::get_payment_count(env.clone(), contract.id);
+        env.events().publish((symbol_short!("PAYSLIP"), contract.id), (seq, amount, payslip.deducted, payslip.net, payslip.fee));
+
+        let record = PaymentRecord {
+            seq,
+            amount,
+            deducted: payslip.deducted,
+            net: payslip.net,
+            fee: payslip.fee,
+            asset: contract.asset.clone(),
+            timestamp: env.ledger().timestamp(),
+            kind,
+            milestone_id,
+            memo,
+        };
+        Self::persist(env, &DataKey::Payment(contract.id, seq), &record);
+        Self::persist(env, &DataKey::PaymentCount(contract.id), &(seq + 1));
+    }
+
+    fn next_payroll_run_id(env: &Env) -> u32 {
+        let run_id: u32 = env.storage().instance().get(&DataKey::NextPayrollRunId).unwrap_or(1);
+        env.storage().instance().set(&DataKey::NextPayrollRunId, &(run_id + 1));
+        run_id
+    }
+
+    /// Pays the items of payroll run `run_id`, draws on the vault once per
+    /// asset and emits the run's summary event. Keeper pools of contracts the
+    /// run completes are released last, once the transfers are made; a vault
+    /// failure at that stage fails the whole run.
+    fn pay_payroll(
+        env: &Env,
+        caller: &Address,
+        run_id: u32,
+        items: &Vec<PayrollItem>,
+        mode: &PayrollMode,
+    ) -> Result<Vec<PayrollOutcome>, LifecycleError> {
+        let mut transfers: Map<Address, Vec<ReservedPayout>> = Map::new(env);
+        let mut completed = Vec::new(env);
+        let mut outcomes = Vec::new(env);
+        let (mut paid, mut skipped) = (0u32, 0u32);
+        for item in items.iter() {
+            match Self::pay_payroll_item(env, caller, &item, &mut transfers, &mut completed) {
+                Ok(amount) => {
+                    outcomes.push_back(PayrollOutcome { contract_id: item.contract_id, paid: amount, skipped: None });
+                    paid += 1;
+                }
+                Err(error) if *mode == PayrollMode::BestEffort => {
+                    outcomes.push_back(PayrollOutcome { contract_id: item.contract_id, paid: 0, skipped: Some(error as u32) });
+                    skipped += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut totals = Vec::new(env);
+        for (asset, payouts) in transfers.iter() {
+            Self::vault_client(env)?.try_pay_reserved_batch(&asset, &payouts)
+                .map_err(|_| LifecycleError::VaultPaymentFailed)?
+                .map_err(|_| LifecycleError::VaultPaymentFailed)?;
+            let total: i128 = payouts.iter().flat_map(|payout| payout.payments.iter()).map(|payment| payment.amount).sum();
+            totals.push_back((asset, total));
+        }
+        for contract_id in completed.iter() {
+            Self::release_keeper_pool(env, contract_id)?;
+        }
+
+        env.events().publish((symbol_short!("PAYROLL"), run_id), (caller.clone(), paid, skipped, totals));
+        Ok(outcomes)
+    }
+
+    /// Freezes the payees, assets and amounts of a drafted run, with a total
+    /// per asset. Only set amounts can be drafted, on the employer's own
+    /// active contracts.
+    fn snapshot_payroll(
+        env: &Env,
+        employer: &Address,
+        items: &Vec<PayrollItem>,
+    ) -> Result<(Vec<PayrollLine>, Vec<AssetTotal>), LifecycleError> {
+        if items.is_empty() || items.len() > MAX_PAYROLL_ITEMS { return Err(LifecycleError::InvalidPayrollRun); }
+
+        let mut lines = Vec::new(env);
+        let mut sums: Map<Address, i128> = Map::new(env);
+        for item in items.iter() {
+            let PayrollAmount::Amount(amount) = item.amount else { return Err(LifecycleError::InvalidPayrollRun); };
+            if amount <= 0 { return Err(LifecycleError::InvalidAmount); }
+
+            let contract = Self::get_contract_internal(env, item.contract_id)?;
+            if contract.employer != *employer { return Err(LifecycleError::NotAuthorized); }
+            if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+            sums.set(contract.asset.clone(), sums.get(contract.asset.clone()).unwrap_or(0) + amount);
+            lines.push_back(PayrollLine { contract_id: contract.id, payee: contract.employee, asset: contract.asset, amount });
+        }
+
+        let mut totals = Vec::new(env);
+        for (asset, amount) in sums.iter() {
+            totals.push_back(AssetTotal { asset, amount });
+        }
+        Ok((lines, totals))
+    }
+
+    /// A payroll run that can still be edited, approved or cancelled.
+    fn get_open_payroll_run(env: &Env, run_id: u32) -> Result<PayrollRun, LifecycleError> {
+        let run = Self::get_payroll_run(env.clone(), run_id).ok_or(LifecycleError::PayrollRunNotFound)?;
+        if run.status != PayrollRunStatus::Draft && run.status != PayrollRunStatus::Approved {
+            return Err(LifecycleError::InvalidPayrollRunState);
+        }
+        Ok(run)
+    }
+
+    /// Pays one item of a payroll run, queueing its transfers in `transfers`
+    /// by asset and, if it completes the contract, its id in `completed`.
+    /// Every check runs before the first write, including whether the
+    /// contract's reservation still covers the transfers already queued
+    /// against it, so a skipped item leaves no trace.
+    fn pay_payroll_item(
+        env: &Env,
+        caller: &Address,
+        item: &PayrollItem,
+        transfers: &mut Map<Address, Vec<ReservedPayout>>,
+        completed: &mut Vec<u32>,
+    ) -> Result<i128, LifecycleError> {
+        let mut contract = Self::get_contract_internal(env, item.contract_id)?;
+        let delegation = Self::authorize_employer(env, &contract.employer, caller, OperatorScope::ProcessPayments)?;
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let (amount, kind, schedule) = match (&contract.contract_type, &item.amount) {
+            (ContractType::FixedRate, PayrollAmount::Amount(amount)) => (*amount, PaymentKind::Fixed, None),
+            (ContractType::FixedRate, PayrollAmount::DuePeriods) => {
+                let (mut schedule, due, amount) = Self::scheduled_amount(env, contract.id)?;
+                schedule.periods_paid = due;
+                (amount, PaymentKind::Scheduled, Some(schedule))
+            }
+            (ContractType::PayAsYouGo, PayrollAmount::Amount(amount)) if !Self::is_metered(env, contract.id) => {
+                (*amount, PaymentKind::PayAsYouGo, None)
+            }
+            _ => return Err(LifecycleError::InvalidContractType),
+        };
+        if amount <= 0 { return Err(LifecycleError::InvalidAmount); }
+        if amount > contract.total_amount - contract.paid_amount { return Err(LifecycleError::InsufficientContractFunds); }
+
+        let payslip = Self::plan_payout(env, &contract, amount)?;
+        let usage = Self::operator_usage(env, &contract.employer, caller, delegation, amount)?;
+
+        let mut queued = transfers.get(contract.asset.clone()).unwrap_or(Vec::new(env));
+        let pending: i128 = queued.iter()
+            .filter(|payout| payout.contract_id == contract.id)
+            .flat_map(|payout| payout.payments.iter())
+            .chain(payslip.payments.iter())
+            .map(|payment| payment.amount)
+            .sum();
+        let reservation = Self::vault_client(env)?.get_reservation(&contract.id).ok_or(LifecycleError::VaultPaymentFailed)?;
+        if reservation.asset != contract.asset || pending > reservation.reserved - reservation.spent {
+            return Err(LifecycleError::VaultPaymentFailed);
+        }
+
+        // Nothing below can fail.
+        if let Some(usage) = usage {
+            Self::persist(env, &DataKey::OperatorUsage(contract.employer.clone(), caller.clone()), &usage);
+        }
+        if !payslip.payments.is_empty() {
+            queued.push_back(ReservedPayout { contract_id: contract.id, payments: payslip.payments.clone() });
+            transfers.set(contract.asset.clone(), queued);
+        }
+
+        Self::record_payout(env, &contract, amount, &payslip, kind, None, None);
+        if let Some(schedule) = schedule {
+            Self::persist(env, &DataKey::Schedule(contract.id), &schedule);
+        }
+
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+            completed.push_back(contract.id);
+        }
+        Self::persist(env, &DataKey::Contract(contract.id), &contract);
+        Ok(amount)
+    }
+
+    /// Pays every period of a FixedRate contract's schedule that has fallen
+    /// due. Returns the number of periods and the amount paid.
+    fn pay_scheduled(env: &Env, contract: &mut Contract) -> Result<(u32, i128), LifecycleError> {
+        if contract.contract_type != ContractType::FixedRate { return Err(LifecycleError::InvalidContractType); }
+        if contract.status != ContractStatus::Active { return Err(LifecycleError::ContractNotActive); }
+
+        let (mut schedule, due, amount) = Self::scheduled_amount(env, contract.id)?;
+        let periods = due - schedule.periods_paid;
+        let remaining = contract.total_amount - contract.paid_amount;
+        if amount > remaining { return Err(LifecycleError::InsufficientContractFunds); }
+
+        Self::pay_employee(env, contract, amount, PaymentKind::Scheduled, None, None)?;
+
+        let first_period = schedule.periods_paid;
+        schedule.periods_paid = due;
+        contract.paid_amount += amount;
+        if contract.paid_amount >= contract.total_amount {
+            contract.status = ContractStatus::Completed;
+            Self::release_keeper_pool(env, contract.id)?;
+        }
+
+        Self::persist(env, &DataKey::Contract(contract.id), contract);
+        Self::persist(env, &DataKey::Schedule(contract.id), &schedule);
+        env.events().publish(
+            (symbol_short!("SCHEDULED"), contract.id),
+            (contract.employee.clone(), first_period, due, amount),
+        );
+
+        Ok((periods, amount))
+    }
+
+    /// Withdraws keeper authorization and hands the unused reward pool back
+    /// to the employer.
+    fn release_keeper_pool(env: &Env, contract_id: u32) -> Result<(), LifecycleError> {
+        let Some(terms) = Self::get_keeper_terms(env.clone(), contract_id) else { return Ok(()); };
+
+        Self::release_funds(env, contract_id, terms.reserved)?;
+        env.storage().persistent().remove(&DataKey::KeeperTerms(contract_id));
+        Ok(())
+    }
+
+    /// The contract's schedule with the number of periods due by now and what
+    /// paying the unpaid ones comes to.
+    fn scheduled_amount(env: &Env, contract_id: u32) -> Result<(PaymentSchedule, u32, i128), LifecycleError> {
+        let schedule: PaymentSchedule = env.storage()
+            .persistent()
+            .get(&DataKey::Schedule(contract_id))
+            .ok_or(LifecycleError::InvalidSchedule)?;
+        let due = Self::periods_due(&schedule, env.ledger().timestamp());
+        if due <= schedule.periods_paid { return Err(LifecycleError::NoPaymentDue); }
+
+        let amount = schedule.amount_per_period * (due - schedule.periods_paid) as i128;
+        Ok((schedule, due, amount))
+    }
+
+    /// Divides `amount` between team payees: fixed shares first, then the
+    /// rest by percentage, with rounding dust going to the last percentage payee.
+    fn split_payment(env: &Env, payees: &Vec<Payee>, amount: i128) -> Result<Vec<Payment>, LifecycleError> {
+        let mut fixed_total: i128 = 0;
+        let mut last_percent = None;
+        for (index, payee) in payees.iter().enumerate() {
+            match payee.rule {
+                SplitRule::Fixed(share) => fixed_total += share,
+                SplitRule::Percent(_) => last_percent = Some(index as u32),
+            }
+        }
+
+        let rest = amount - fixed_total;
+        if rest < 0 || (rest > 0 && last_percent.is_none()) { return Err(LifecycleError::InvalidSplitRule); }
+
+        let mut payments = Vec::new(env);
+        let mut distributed: i128 = 0;
+        for (index, payee) in payees.iter().enumerate() {
+            let share = match payee.rule {
+                SplitRule::Fixed(share) => share,
+                SplitRule::Percent(_) if last_percent == Some(index as u32) => rest - distributed,
+                SplitRule::Percent(bps) => {
+                    let share = rest * bps as i128 / BASIS_POINTS as i128;
+                    distributed += share;
+                    share
+                }
+            };
+            if share > 0 { payments.push_back(Payment { recipient: payee.address, amount: share }); }
+        }
+
+        Ok(payments)
+    }
+
+    fn pay_from_reservation(
+        env: &Env,
+        contract_id: u32,
+        recipient: &Address,
+        amount: i128,
+    ) -> Result<(), LifecycleError> {
         // Use the 'try_' version of the client method to catch contract errors
-        vault_client.try_withdraw_available(recipient, &amount, asset)
+        Self::vault_client(env)?.try_pay_reserved(&contract_id, recipient, &amount)
             .map_err(|_| LifecycleError::VaultPaymentFailed)? // Catches Invocation error
             .map_err(|_| LifecycleError::VaultPaymentFailed)?; // Catches logic error if applicable
 
         Ok(())
     }
+
+    /// Keeps the vault reservation equal to what an amended contract still owes,
+    /// less the outstanding advance that has already been paid out of it.
+    /// Switching assets withdraws keeper authorization, since the reward pool
+    /// is held in the old asset; the employer can set a new reward afterwards.
+    fn rebalance_reservation(env: &Env, before: &Contract, after: &Contract) -> Result<(), LifecycleError> {
+        let advance = Self::get_outstanding_advance(env.clone(), before.id);
+        let owed_before = before.total_amount - before.paid_amount - advance;
+        let owed_after = after.total_amount - after.paid_amount - advance;
+
+        if before.asset != after.asset {
+            Self::release_keeper_pool(env, before.id)?;
+            Self::release_funds(env, before.id, owed_before)?;
+            return Self::reserve_funds(env, after.id, &after.asset, owed_after);
+        }
+
+        if owed_after > owed_before {
+            Self::reserve_funds(env, after.id, &after.asset, owed_after - owed_before)
+        } else {
+            Self::release_funds(env, after.id, owed_before - owed_after)
+        }
+    }
 }
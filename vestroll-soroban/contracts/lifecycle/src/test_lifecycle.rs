@@ -1,15 +1,21 @@
 #![cfg(test)]
 
 use soroban_sdk::{
-    testutils::{Address as _, Ledger as _}, 
-    token, Address, Env, String, Vec
+    testutils::{storage::Persistent as _, Address as _, Ledger as _},
+    symbol_short, token, Address, BytesN, Env, String, Vec
 };
 
-use vestroll_common::{ContractType, ContractStatus};
+use vestroll_common::{ContractType, ContractStatus, ContractMetadata, FeeConfig, LifecycleError, UpgradeKey, RECORD_BUMP_AMOUNT};
+use vestroll_identity::{IdentityManagementContract, IdentityManagementContractClient, OperatorScope};
 use vestroll_profile::{ProfileContract, ProfileContractClient};
 use vestroll_vault::{VaultContract, VaultContractClient};
 
-use crate::{LifecycleContract, LifecycleContractClient, Milestone};
+use crate::{
+    AdvancePolicy, AdvanceStatus, AmendmentChanges, ClaimStatus, ContractFilter, DataKey, LegacyContract, LifecycleContract, Deduction, Payee, PaymentKind, PayrollAmount, PayrollItem, PayrollApprovers, PayrollMode, PayrollRunStatus, SplitRule, LifecycleContractClient, Milestone, PaymentCadence, PaymentSchedule,
+    ReviewAction, TerminationTerms, TimesheetStatus, UsageStatus, UsageTerms,
+};
+
+const OFFER_EXPIRY: u64 = 100_000;
 
 fn setup_env() -> (Env, Address, Address, Address) {
     let env = Env::default();
@@ -49,8 +55,8 @@ fn setup_contracts(
     let vault_id = env.register(VaultContract, ());
     let vault_client = VaultContractClient::new(env, &vault_id);
    
-    let admin = Address::generate(&env);     
-    let token_admin = Address::generate(&env); 
+    let admin = Address::generate(env);
+    let token_admin = Address::generate(env);
 
     let token_address = env.register_stellar_asset_contract_v2(token_admin.clone()).address();
 
@@ -66,6 +72,7 @@ fn setup_contracts(
     let lifecycle_id = env.register(LifecycleContract, ());
     let lifecycle_client = LifecycleContractClient::new(env, &lifecycle_id);
     lifecycle_client.initialize(&admin, &vault_id, &profile_id, &token_address);
+    vault_client.set_lifecycle_contract(&admin, &lifecycle_id);
     
     (lifecycle_client, vault_id, profile_id, token_address, token_client)
 }
@@ -85,11 +92,6 @@ fn test_process_fixed_payment() {
         asset: token_address.clone(),
     };
 
-    let contract_id = lifecycle_client.create_contract(
-        &employer, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None,
-    );
-    
-   
     token_client.approve(&employer, &employer, &3000, &10000);
     let vault_client = VaultContractClient::new(&env, &vault_id);
     
@@ -98,9 +100,14 @@ fn test_process_fixed_payment() {
  
     token_client.transfer(&employer, &vault_id, &2000); 
 
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
     env.mock_all_auths_allowing_non_root_auth();
     
-    lifecycle_client.process_fixed_payment(&employer, &contract_id, &500);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &500, &None);
     
     assert_eq!(token_client.balance(&employee), 500);
 }
@@ -124,20 +131,3140 @@ fn test_milestone_flow() {
         asset: token_address.clone(),
     };
 
-    let contract_id = lifecycle_client.create_contract(
-        &employer, &employee, &ContractType::Milestone, &1000, &token_address, &metadata, &Some(milestones),
-    );
-    
     token_client.approve(&employer, &employer, &2000, &10000);
     let vault_client = VaultContractClient::new(&env, &vault_id);
     
     vault_client.deposit(&employer, &1000, &token_address);
     token_client.transfer(&employer, &vault_id, &1000); // Buffer for "available" liquidity
 
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &1000, &token_address, &metadata, &Some(milestones), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
     env.mock_all_auths_allowing_non_root_auth();
     
     lifecycle_client.complete_milestone(&employer, &contract_id, &1);
-    lifecycle_client.process_milestone_payment(&employer, &contract_id, &1);
+    lifecycle_client.process_milestone_payment(&employer, &contract_id, &1, &None);
     
     assert_eq!(token_client.balance(&employee), 300);
+    assert_eq!(
+        lifecycle_client.try_process_milestone_payment(&employer, &contract_id, &1, &None),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+}
+
+fn create_metadata(
+    employer: &Address,
+    employee: &Address,
+    contract_type: ContractType,
+    amount: i128,
+    asset: &Address,
+) -> ContractMetadata {
+    ContractMetadata {
+        employer: employer.clone(),
+        employee: employee.clone(),
+        contract_type,
+        status: ContractStatus::Active,
+        amount,
+        asset: asset.clone(),
+    }
+}
+
+// Mints unallocated liquidity straight into the vault for contracts to reserve.
+fn fund_vault(env: &Env, vault_id: &Address, token_address: &Address, amount: i128) {
+    token::StellarAssetClient::new(env, token_address).mint(vault_id, &amount);
+}
+
+fn weekly_schedule(amount_per_period: i128, start_time: u64, weeks: u64) -> PaymentSchedule {
+    PaymentSchedule {
+        cadence: PaymentCadence::Weekly,
+        amount_per_period,
+        start_time,
+        end_time: start_time + weeks * 7 * 86_400,
+        periods_paid: 0,
+    }
+}
+
+#[test]
+fn test_scheduled_payment_pays_due_periods() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let schedule = weekly_schedule(100, 1_000, 4);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None, &Some(schedule), &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Two and a half weeks in: two full periods are due.
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 17 * 86_400);
+    assert_eq!(lifecycle_client.process_scheduled_payment(&employer, &contract_id), 2);
+    assert_eq!(token_client.balance(&employee), 200);
+
+    // Long after the end only the remaining periods are paid.
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 100 * 86_400);
+    assert_eq!(lifecycle_client.process_scheduled_payment(&employer, &contract_id), 2);
+    assert_eq!(token_client.balance(&employee), 400);
+
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.paid_amount, 400);
+    assert_eq!(contract.status, ContractStatus::Completed);
+    assert_eq!(lifecycle_client.get_payment_schedule(&contract_id).unwrap().periods_paid, 4);
+}
+
+#[test]
+fn test_scheduled_payment_rejects_double_payment() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Nothing is due before the first period ends.
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 6 * 86_400);
+    assert_eq!(
+        lifecycle_client.try_process_scheduled_payment(&employer, &contract_id),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 7 * 86_400);
+    lifecycle_client.process_scheduled_payment(&employer, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_process_scheduled_payment(&employer, &contract_id),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+    assert_eq!(token_client.balance(&employee), 100);
+}
+
+#[test]
+fn test_create_contract_rejects_invalid_schedule() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 300, &token_address);
+
+    // Four weekly periods of 100 exceed the 300 contract total.
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::FixedRate, &300, &token_address, &metadata, &None,
+            &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InsufficientContractFunds))
+    );
+
+    // A window shorter than one period has nothing to pay.
+    let mut short = weekly_schedule(100, 1_000, 1);
+    short.end_time -= 1;
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::FixedRate, &300, &token_address, &metadata, &None,
+            &Some(short), &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InvalidSchedule))
+    );
+
+    // Schedules only apply to FixedRate contracts.
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::PayAsYouGo, &400, &token_address, &metadata, &None,
+            &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InvalidContractType))
+    );
+}
+
+#[test]
+fn test_withdraw_streamed_accrues_linearly() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_stream(
+        &employer, &employee, &1_000, &token_address, &metadata, &1_000, &2_000, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_250);
+    assert_eq!(lifecycle_client.get_streamed_balance(&contract_id), 250);
+    assert_eq!(lifecycle_client.withdraw_streamed(&employee, &contract_id), 250);
+    assert_eq!(token_client.balance(&employee), 250);
+
+    // Nothing new has accrued within the same ledger second.
+    assert_eq!(
+        lifecycle_client.try_withdraw_streamed(&employee, &contract_id),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+
+    // Accrual stops at the end of the stream.
+    env.ledger().with_mut(|li| li.timestamp = 5_000);
+    assert_eq!(lifecycle_client.withdraw_streamed(&employee, &contract_id), 750);
+    assert_eq!(token_client.balance(&employee), 1_000);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Completed);
+}
+
+#[test]
+fn test_withdraw_streamed_only_by_employee() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_stream(
+        &employer, &employee, &1_000, &token_address, &metadata, &1_000, &2_000, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_500);
+    assert_eq!(
+        lifecycle_client.try_withdraw_streamed(&employer, &contract_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // Streams can only be created through `create_stream`, with a valid window.
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::Streaming, &1_000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InvalidContractType))
+    );
+    assert_eq!(
+        lifecycle_client.try_create_stream(
+            &employer, &employee, &1_000, &token_address, &metadata, &2_000, &2_000, &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InvalidSchedule))
+    );
+}
+
+#[test]
+fn test_offer_acceptance_flow() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1_000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Draft);
+
+    // No money moves before the employee has agreed to the terms.
+    assert_eq!(
+        lifecycle_client.try_process_payg_payment(&employer, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+
+    // Only the named employee can accept.
+    assert_eq!(
+        lifecycle_client.try_accept_contract(&employer, &contract_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 500);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.status, ContractStatus::Active);
+    assert_eq!(contract.accepted_at, Some(500));
+
+    assert_eq!(
+        lifecycle_client.try_accept_contract(&employee, &contract_id),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    lifecycle_client.process_payg_payment(&employer, &contract_id, &100, &None);
+    assert_eq!(token_client.balance(&employee), 100);
+}
+
+#[test]
+fn test_offer_rejection_and_expiry() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 1_000, &token_address);
+    let rejected = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1_000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.reject_contract(&employee, &rejected);
+    assert_eq!(lifecycle_client.get_contract(&rejected).status, ContractStatus::Cancelled);
+    assert_eq!(
+        lifecycle_client.try_accept_contract(&employee, &rejected),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    let expired = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1_000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    env.ledger().with_mut(|li| li.timestamp = OFFER_EXPIRY + 1);
+    assert_eq!(
+        lifecycle_client.try_accept_contract(&employee, &expired),
+        Err(Ok(LifecycleError::OfferExpired))
+    );
+
+    // Sweeping closes the lapsed offer and frees its reservation.
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 1_000);
+    assert_eq!(lifecycle_client.sweep_expired(&Vec::from_array(&env, [rejected, expired])), Vec::from_array(&env, [expired]));
+    assert_eq!(lifecycle_client.get_contract(&expired).status, ContractStatus::Expired);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(lifecycle_client.sweep_expired(&Vec::from_array(&env, [expired])), Vec::new(&env));
+
+    // The deadline has to be in the future when the offer is made.
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::FixedRate, &1_000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::OfferExpired))
+    );
+}
+
+// Creates an offer of the given type and has the employee accept it.
+fn create_active_contract(
+    client: &LifecycleContractClient,
+    employer: &Address,
+    employee: &Address,
+    contract_type: ContractType,
+    amount: i128,
+    asset: &Address,
+) -> u32 {
+    let metadata = create_metadata(employer, employee, contract_type.clone(), amount, asset);
+    let contract_id = client.create_contract(
+        employer, employee, &contract_type, &amount, asset, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    client.accept_contract(employee, &contract_id);
+    contract_id
+}
+
+fn no_changes() -> AmendmentChanges {
+    AmendmentChanges { total_amount: None, asset: None, add_milestones: None, end_date: None }
+}
+
+#[test]
+fn test_amendment_applies_and_keeps_history() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1_000, &token_address,
+    );
+
+    let mut changes = no_changes();
+    changes.total_amount = Some(1_500);
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+
+    // A second proposal has to wait for the first to be resolved.
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employee, &contract_id, &changes),
+        Err(Ok(LifecycleError::AmendmentPending))
+    );
+
+    // The proposer cannot approve their own change.
+    assert_eq!(
+        lifecycle_client.try_approve_amendment(&employer, &contract_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    assert_eq!(lifecycle_client.approve_amendment(&employee, &contract_id), 2);
+    assert!(lifecycle_client.get_pending_amendment(&contract_id).is_none());
+
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.total_amount, 1_500);
+    assert_eq!(contract.version, 2);
+
+    let original = lifecycle_client.get_contract_version(&contract_id, &1);
+    assert_eq!(original.total_amount, 1_000);
+
+    let history = lifecycle_client.get_contract_history(&contract_id);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.get(0).unwrap().total_amount, 1_000);
+    assert_eq!(history.get(1).unwrap().total_amount, 1_500);
+}
+
+#[test]
+fn test_amendment_adds_milestones_and_rejection() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+
+    let milestones = Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "P1"), amount: 300, completed: false, completed_at: None },
+    ]);
+    let metadata = create_metadata(&employer, &employee, ContractType::Milestone, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &1_000, &token_address, &metadata, &Some(milestones), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Duplicate milestone ids are refused at proposal time.
+    let mut changes = no_changes();
+    changes.add_milestones = Some(Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "Dup"), amount: 100, completed: false, completed_at: None },
+    ]));
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employee, &contract_id, &changes),
+        Err(Ok(LifecycleError::InvalidMilestoneData))
+    );
+
+    changes.add_milestones = Some(Vec::from_array(&env, [
+        Milestone { id: 2, description: String::from_str(&env, "P2"), amount: 200, completed: true, completed_at: Some(1) },
+    ]));
+    lifecycle_client.propose_amendment(&employee, &contract_id, &changes);
+    lifecycle_client.reject_amendment(&employer, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_approve_amendment(&employer, &contract_id),
+        Err(Ok(LifecycleError::AmendmentNotFound))
+    );
+
+    lifecycle_client.propose_amendment(&employee, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employer, &contract_id);
+
+    let milestones = lifecycle_client.get_contract(&contract_id).milestones.unwrap();
+    assert_eq!(milestones.len(), 2);
+    assert!(!milestones.get(1).unwrap().completed);
+
+    // Empty change sets are meaningless.
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employer, &contract_id, &no_changes()),
+        Err(Ok(LifecycleError::InvalidAmendment))
+    );
+}
+
+#[test]
+fn test_amendment_rejects_overcommitted_milestones_and_stale_versions() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let milestone = |id: u32, amount: i128| Milestone {
+        id, description: String::from_str(&env, "Phase"), amount, completed: false, completed_at: None,
+    };
+
+    // Milestones may not promise more than the contract holds.
+    let metadata = create_metadata(&employer, &employee, ContractType::Milestone, 1_000, &token_address);
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::Milestone, &1_000, &token_address, &metadata,
+            &Some(Vec::from_array(&env, [milestone(1, 600), milestone(2, 500)])), &None, &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InvalidMilestoneData))
+    );
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &1_000, &token_address, &metadata,
+        &Some(Vec::from_array(&env, [milestone(1, 600)])), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let mut changes = no_changes();
+    changes.add_milestones = Some(Vec::from_array(&env, [milestone(2, 500)]));
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employee, &contract_id, &changes),
+        Err(Ok(LifecycleError::InvalidMilestoneData))
+    );
+    changes.add_milestones = None;
+    changes.total_amount = Some(500);
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employer, &contract_id, &changes),
+        Err(Ok(LifecycleError::InvalidMilestoneData))
+    );
+
+    // Raising the total and adding the milestone together is fine.
+    changes.total_amount = Some(1_100);
+    changes.add_milestones = Some(Vec::from_array(&env, [milestone(2, 500)]));
+    lifecycle_client.propose_amendment(&employee, &contract_id, &changes);
+    let stale = lifecycle_client.get_pending_amendment(&contract_id).unwrap();
+    assert_eq!(lifecycle_client.approve_amendment(&employer, &contract_id), 2);
+
+    // An amendment drafted against version 1 cannot be applied to version 2.
+    env.as_contract(&lifecycle_client.address, || {
+        env.storage().persistent().set(&DataKey::PendingAmendment(contract_id), &stale);
+    });
+    assert_eq!(
+        lifecycle_client.try_approve_amendment(&employer, &contract_id),
+        Err(Ok(LifecycleError::InvalidAmendment))
+    );
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!((contract.version, contract.total_amount), (2, 1_100));
+    assert_eq!(contract.milestones.unwrap().len(), 2);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 1_100);
+}
+
+#[test]
+fn test_milestone_submission_review_flow() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let milestones = Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "P1"), amount: 300, completed: false, completed_at: None },
+    ]);
+    let metadata = create_metadata(&employer, &employee, ContractType::Milestone, 300, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &300, &token_address, &metadata, &Some(milestones), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Nothing to review before the worker submits.
+    assert_eq!(
+        lifecycle_client.try_approve_milestone(&employer, &contract_id, &1),
+        Err(Ok(LifecycleError::MilestoneNotSubmitted))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 100);
+    lifecycle_client.submit_milestone(&employee, &contract_id, &1, &String::from_str(&env, "ipfs://draft"));
+    assert_eq!(
+        lifecycle_client.try_submit_milestone(&employee, &contract_id, &1, &String::from_str(&env, "ipfs://again")),
+        Err(Ok(LifecycleError::MilestoneAlreadySubmitted))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 200);
+    lifecycle_client.reject_milestone(&employer, &contract_id, &1, &String::from_str(&env, "missing tests"));
+    assert_eq!(
+        lifecycle_client.try_process_milestone_payment(&employer, &contract_id, &1, &None),
+        Err(Ok(LifecycleError::MilestoneNotCompleted))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 300);
+    lifecycle_client.submit_milestone(&employee, &contract_id, &1, &String::from_str(&env, "ipfs://final"));
+    env.ledger().with_mut(|li| li.timestamp = 400);
+    lifecycle_client.approve_milestone(&employer, &contract_id, &1);
+    lifecycle_client.process_milestone_payment(&employer, &contract_id, &1, &None);
+    assert_eq!(token_client.balance(&employee), 300);
+
+    let history = lifecycle_client.get_milestone_history(&contract_id, &1);
+    assert_eq!(history.len(), 4);
+    let rejection = history.get(1).unwrap();
+    assert_eq!(rejection.action, ReviewAction::Rejected);
+    assert_eq!(rejection.note, String::from_str(&env, "missing tests"));
+    assert_eq!(rejection.timestamp, 200);
+    let approval = history.get(3).unwrap();
+    assert_eq!(approval.action, ReviewAction::Approved);
+    assert_eq!(approval.actor, employer);
+}
+
+#[test]
+fn test_milestone_submission_permissions() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+
+    let milestones = Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "P1"), amount: 300, completed: false, completed_at: None },
+    ]);
+    let metadata = create_metadata(&employer, &employee, ContractType::Milestone, 300, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &300, &token_address, &metadata, &Some(milestones), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let deliverable = String::from_str(&env, "sha256:abc");
+    assert_eq!(
+        lifecycle_client.try_submit_milestone(&employer, &contract_id, &1, &deliverable),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_submit_milestone(&employee, &contract_id, &9, &deliverable),
+        Err(Ok(LifecycleError::MilestoneNotFound))
+    );
+
+    lifecycle_client.submit_milestone(&employee, &contract_id, &1, &deliverable);
+    assert_eq!(
+        lifecycle_client.try_approve_milestone(&employee, &contract_id, &1),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_contract_dispute_freezes_and_splits() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1_000, &token_address,
+    );
+    lifecycle_client.process_payg_payment(&employer, &contract_id, &200, &None);
+
+    lifecycle_client.raise_dispute(&employee, &contract_id, &None, &String::from_str(&env, "unpaid overtime"));
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Disputed);
+    assert_eq!(lifecycle_client.get_dispute(&contract_id).unwrap().amount, 800);
+
+    // Payments and unilateral cancellation are frozen while disputed.
+    assert_eq!(
+        lifecycle_client.try_process_payg_payment(&employer, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(
+        lifecycle_client.try_cancel_contract(&employer, &contract_id),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+
+    // Neither party can rule on their own dispute.
+    assert_eq!(
+        lifecycle_client.try_resolve_dispute(&employer, &contract_id, &0),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    let arbiter = lifecycle_client.get_admin();
+    assert_eq!(
+        lifecycle_client.try_resolve_dispute(&arbiter, &contract_id, &801),
+        Err(Ok(LifecycleError::InvalidAmount))
+    );
+    lifecycle_client.resolve_dispute(&arbiter, &contract_id, &500);
+
+    assert_eq!(token_client.balance(&employee), 700);
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.paid_amount, 700);
+    assert_eq!(contract.total_amount, 700);
+    assert_eq!(contract.status, ContractStatus::Completed);
+    assert!(lifecycle_client.get_dispute(&contract_id).is_none());
+}
+
+#[test]
+fn test_milestone_dispute_resolved_by_identity_manager() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let manager = Address::generate(&env);
+    let identity_id = env.register(IdentityManagementContract, ());
+    let identity_client = IdentityManagementContractClient::new(&env, &identity_id);
+    identity_client.initialize(&admin);
+    identity_client.add_manager(&admin, &manager);
+    lifecycle_client.set_identity_contract(&lifecycle_client.get_admin(), &identity_id);
+
+    let milestones = Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "P1"), amount: 300, completed: false, completed_at: None },
+        Milestone { id: 2, description: String::from_str(&env, "P2"), amount: 700, completed: false, completed_at: None },
+    ]);
+    let metadata = create_metadata(&employer, &employee, ContractType::Milestone, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::Milestone, &1_000, &token_address, &metadata, &Some(milestones), &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    lifecycle_client.raise_dispute(&employer, &contract_id, &Some(1), &String::from_str(&env, "incomplete"));
+    assert_eq!(
+        lifecycle_client.try_resolve_dispute(&Address::generate(&env), &contract_id, &100),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.resolve_dispute(&manager, &contract_id, &100);
+
+    assert_eq!(token_client.balance(&employee), 100);
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.status, ContractStatus::Active);
+    assert_eq!(contract.total_amount, 800);
+    assert!(contract.milestones.unwrap().get(0).unwrap().completed);
+
+    // The award settled the milestone, so it can be neither paid nor disputed again.
+    assert_eq!(lifecycle_client.get_paid_milestones(&contract_id), Vec::from_array(&env, [1u32]));
+    assert_eq!(
+        lifecycle_client.try_process_milestone_payment(&employer, &contract_id, &1, &None),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+    assert_eq!(
+        lifecycle_client.try_raise_dispute(&employee, &contract_id, &Some(1), &String::from_str(&env, "again")),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+    assert_eq!(token_client.balance(&employee), 100);
+}
+
+#[test]
+fn test_contract_funds_reserved_in_vault() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 1_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 600, &token_address,
+    );
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (600, 0));
+
+    // The employer cannot promise the same liquidity twice.
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 600, &token_address);
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &employer, &employee, &ContractType::PayAsYouGo, &600, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+        ),
+        Err(Ok(LifecycleError::InsufficientVaultFunds))
+    );
+
+    lifecycle_client.process_payg_payment(&employer, &contract_id, &200, &None);
+    assert_eq!(token_client.balance(&employee), 200);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().spent, 200);
+
+    // Cancelling hands the unspent 400 back to the vault.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (200, 200));
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 800);
+}
+
+#[test]
+fn test_amendment_rebalances_reservation() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 1_000);
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 500, &token_address,
+    );
+
+    let mut changes = no_changes();
+    changes.total_amount = Some(900);
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employee, &contract_id);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 900);
+
+    changes.total_amount = Some(300);
+    lifecycle_client.propose_amendment(&employee, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employer, &contract_id);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 300);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 300);
+}
+
+#[test]
+fn test_employer_termination_pays_notice_and_severance() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1_000, &token_address);
+    let contract_id = lifecycle_client.create_stream(
+        &employer, &employee, &1_000, &token_address, &metadata, &1_000, &2_000, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_termination_terms(
+        &employer, &contract_id, &TerminationTerms { notice_period: 100, severance: 50 },
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Terms are frozen once the employee has accepted.
+    assert_eq!(
+        lifecycle_client.try_set_termination_terms(
+            &employer, &contract_id, &TerminationTerms { notice_period: 0, severance: 0 },
+        ),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 1_200);
+    lifecycle_client.withdraw_streamed(&employee, &contract_id);
+    assert_eq!(token_client.balance(&employee), 200);
+
+    // Accrual through the 100s notice period (300) plus 50 severance.
+    env.ledger().with_mut(|li| li.timestamp = 1_300);
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 200 + 200 + 50);
+
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!(contract.status, ContractStatus::Cancelled);
+    assert_eq!(contract.paid_amount, 450);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+}
+
+#[test]
+fn test_resignation_settles_pro_rata() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_termination_terms(
+        &employer, &contract_id, &TerminationTerms { notice_period: 7 * 86_400, severance: 100 },
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    assert_eq!(
+        lifecycle_client.try_resign(&employer, &contract_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // A week and a half in; the first week is paid on schedule.
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 7 * 86_400);
+    lifecycle_client.process_scheduled_payment(&employer, &contract_id);
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 7 * 86_400 + 302_400);
+
+    // Resigning pays the half week worked; no notice pay or severance.
+    lifecycle_client.resign(&employee, &contract_id);
+    assert_eq!(token_client.balance(&employee), 150);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Cancelled);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 150);
+}
+
+#[test]
+fn test_expiry_sweep_releases_reservation() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    assert_eq!(
+        lifecycle_client.try_set_contract_term(&employer, &contract_id, &5_000, &Some(5_000)),
+        Err(Ok(LifecycleError::InvalidSchedule))
+    );
+    lifecycle_client.set_contract_term(&employer, &contract_id, &0, &Some(50_000));
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &400, &None);
+
+    assert_eq!(
+        lifecycle_client.try_expire_contract(&contract_id),
+        Err(Ok(LifecycleError::ContractNotExpired))
+    );
+    assert_eq!(lifecycle_client.sweep_expired(&Vec::from_array(&env, [contract_id])), Vec::new(&env));
+
+    // Past the end date anyone can sweep; the unpaid balance goes back to the employer.
+    env.ledger().with_mut(|li| li.timestamp = 50_000);
+    assert_eq!(lifecycle_client.sweep_expired(&Vec::from_array(&env, [contract_id, 99])), Vec::from_array(&env, [contract_id]));
+
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Expired);
+    assert_eq!(token_client.balance(&employee), 400);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+}
+
+#[test]
+fn test_sweep_skips_contracts_that_cannot_settle() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, profile_id, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let leaver = register_worker(&env, &profile_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1000, &token_address);
+    let paid = lifecycle_client.create_stream(
+        &employer, &employee, &1000, &token_address, &metadata, &0, &20_000, &OFFER_EXPIRY,
+    );
+    // Cutting the term short cuts the stream with it, so nothing vests past the end date.
+    lifecycle_client.set_contract_term(&employer, &paid, &0, &Some(10_000));
+    assert_eq!(lifecycle_client.get_stream(&paid).unwrap().end_time, 10_000);
+    lifecycle_client.accept_contract(&employee, &paid);
+
+    let metadata = create_metadata(&employer, &leaver, ContractType::Streaming, 1000, &token_address);
+    let stuck = lifecycle_client.create_stream(
+        &employer, &leaver, &1000, &token_address, &metadata, &0, &10_000, &OFFER_EXPIRY,
+    );
+    // A flat garnishment larger than the whole contract leaves nothing to settle with.
+    let court = Address::generate(&env);
+    lifecycle_client.set_deductions(&employer, &stuck, &Vec::from_array(&env, [
+        Deduction { name: String::from_str(&env, "Levy"), recipient: court.clone(), rule: SplitRule::Fixed(2_000) },
+    ]));
+    lifecycle_client.accept_contract(&leaver, &stuck);
+
+    env.ledger().with_mut(|li| li.timestamp = 5_000);
+    assert_eq!(lifecycle_client.get_streamed_balance(&paid), 500);
+
+    // The worker who can no longer be paid does not hold up the rest of the sweep.
+    env.ledger().with_mut(|li| li.timestamp = 10_000);
+    assert_eq!(
+        lifecycle_client.sweep_expired(&Vec::from_array(&env, [stuck, paid])),
+        Vec::from_array(&env, [paid])
+    );
+    assert_eq!(lifecycle_client.get_contract(&paid).status, ContractStatus::Expired);
+    assert_eq!(token_client.balance(&employee), 1000);
+    assert_eq!(lifecycle_client.get_contract(&stuck).status, ContractStatus::Active);
+    assert_eq!(token_client.balance(&leaver), 0);
+    assert_eq!(token_client.balance(&court), 0);
+    assert_eq!(unspent_reservation(&vault_client, stuck), 1000);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 1000);
+}
+
+#[test]
+fn test_renewal_extends_stream_end_date() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1000, &token_address);
+    let contract_id = lifecycle_client.create_stream(
+        &employer, &employee, &1000, &token_address, &metadata, &0, &10_000, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).end_date, Some(10_000));
+
+    env.ledger().with_mut(|li| li.timestamp = 5_000);
+    let mut changes = no_changes();
+    changes.end_date = Some(4_000);
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employer, &contract_id, &changes),
+        Err(Ok(LifecycleError::InvalidSchedule))
+    );
+    changes.end_date = Some(20_000);
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).end_date, Some(20_000));
+
+    // The half accrued so far is kept; the rest vests over the new term.
+    let stream = lifecycle_client.get_stream(&contract_id).unwrap();
+    assert_eq!((stream.start_time, stream.end_time, stream.accrued_before), (5_000, 20_000, 500));
+    assert_eq!(lifecycle_client.get_streamed_balance(&contract_id), 500);
+    env.ledger().with_mut(|li| li.timestamp = 12_500);
+    assert_eq!(lifecycle_client.get_streamed_balance(&contract_id), 750);
+    env.ledger().with_mut(|li| li.timestamp = 15_000);
+    assert_eq!(lifecycle_client.sweep_expired(&Vec::from_array(&env, [contract_id])), Vec::new(&env));
+
+    // Expiry pays out whatever accrued and was never withdrawn.
+    env.ledger().with_mut(|li| li.timestamp = 20_000);
+    lifecycle_client.expire_contract(&contract_id);
+    assert_eq!(token_client.balance(&employee), 1000);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Expired);
+}
+
+#[test]
+fn test_renewal_extends_payment_schedule() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 0, 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    env.ledger().with_mut(|li| li.timestamp = 2 * 7 * 86_400);
+    lifecycle_client.process_scheduled_payment(&employer, &contract_id);
+
+    // Four more weeks need the total to cover them too.
+    let mut changes = no_changes();
+    changes.end_date = Some(8 * 7 * 86_400);
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employer, &contract_id, &changes),
+        Err(Ok(LifecycleError::InsufficientContractFunds))
+    );
+    changes.total_amount = Some(800);
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_payment_schedule(&contract_id).unwrap().end_time, 8 * 7 * 86_400);
+
+    // Periods past the original end now fall due.
+    env.ledger().with_mut(|li| li.timestamp = 6 * 7 * 86_400);
+    lifecycle_client.process_scheduled_payment(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 600);
+    assert_eq!(lifecycle_client.get_payment_schedule(&contract_id).unwrap().periods_paid, 6);
+}
+
+#[test]
+fn test_suspended_stream_does_not_accrue() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1000, &token_address);
+    let contract_id = lifecycle_client.create_stream(
+        &employer, &employee, &1000, &token_address, &metadata, &0, &10_000, &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    env.ledger().with_mut(|li| li.timestamp = 2_000);
+    let reason = String::from_str(&env, "unpaid leave");
+    assert_eq!(
+        lifecycle_client.try_suspend_contract(&employee, &contract_id, &reason),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.suspend_contract(&employer, &contract_id, &reason);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::OnHold);
+    assert_eq!(lifecycle_client.get_suspension(&contract_id).unwrap().reason, reason);
+
+    env.ledger().with_mut(|li| li.timestamp = 7_000);
+    assert_eq!(
+        lifecycle_client.try_withdraw_streamed(&employee, &contract_id),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+
+    // Five thousand seconds on hold push the stream out by the same amount.
+    lifecycle_client.resume_contract(&employer, &contract_id);
+    assert_eq!(lifecycle_client.get_suspension(&contract_id), None);
+    assert_eq!(lifecycle_client.get_stream(&contract_id).unwrap().end_time, 15_000);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).end_date, Some(15_000));
+    assert_eq!(
+        lifecycle_client.try_resume_contract(&employer, &contract_id),
+        Err(Ok(LifecycleError::ContractNotOnHold))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 8_000);
+    assert_eq!(lifecycle_client.withdraw_streamed(&employee, &contract_id), 300);
+    assert_eq!(token_client.balance(&employee), 300);
+}
+
+#[test]
+fn test_termination_pays_approved_unpaid_milestones() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, profile_id, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let milestone = |id: u32, amount: i128| Milestone {
+        id, description: String::from_str(&env, "Phase"), amount, completed: false, completed_at: None,
+    };
+    let create = |employee: &Address| {
+        let metadata = create_metadata(&employer, employee, ContractType::Milestone, 1000, &token_address);
+        lifecycle_client.create_contract(
+            &employer, employee, &ContractType::Milestone, &1000, &token_address, &metadata,
+            &Some(Vec::from_array(&env, [milestone(1, 300), milestone(2, 200), milestone(3, 500)])), &None, &OFFER_EXPIRY,
+        )
+    };
+    let deliverable = String::from_str(&env, "ipfs://phase");
+
+    let contract_id = create(&employee);
+    lifecycle_client.set_termination_terms(
+        &employer, &contract_id, &TerminationTerms { notice_period: 0, severance: 50 },
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    lifecycle_client.complete_milestone(&employer, &contract_id, &1);
+    lifecycle_client.process_milestone_payment(&employer, &contract_id, &1, &None);
+    lifecycle_client.submit_milestone(&employee, &contract_id, &2, &deliverable);
+    lifecycle_client.approve_milestone(&employer, &contract_id, &2);
+    lifecycle_client.submit_milestone(&employee, &contract_id, &3, &deliverable);
+
+    // The approved milestone is paid with the severance; the one still under
+    // review is not.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 300 + 200 + 50);
+    assert_eq!(lifecycle_client.get_paid_milestones(&contract_id), Vec::from_array(&env, [1, 2]));
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 550);
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (550, 550));
+
+    // Resigning collects approved milestones too.
+    let worker = register_worker(&env, &profile_id);
+    let contract_id = create(&worker);
+    lifecycle_client.accept_contract(&worker, &contract_id);
+    lifecycle_client.complete_milestone(&employer, &contract_id, &3);
+    lifecycle_client.resign(&worker, &contract_id);
+    assert_eq!(token_client.balance(&worker), 500);
+    assert_eq!(lifecycle_client.get_paid_milestones(&contract_id), Vec::from_array(&env, [3]));
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+}
+
+#[test]
+fn test_cancel_while_suspended_skips_held_time() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 0, 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Suspended half way through the first week, by the admin this time.
+    env.ledger().with_mut(|li| li.timestamp = 302_400);
+    let arbiter = lifecycle_client.get_admin();
+    lifecycle_client.suspend_contract(&arbiter, &contract_id, &String::from_str(&env, "investigation"));
+
+    env.ledger().with_mut(|li| li.timestamp = 4 * 7 * 86_400);
+    assert_eq!(
+        lifecycle_client.try_process_scheduled_payment(&employer, &contract_id),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+
+    // Only the half week worked before the hold is owed.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 50);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Cancelled);
+}
+
+#[test]
+fn test_timesheet_paid_once_at_hourly_rate() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_hourly_rate(&employer, &contract_id, &25);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_process_payg_payment(&employer, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::InvalidContractType))
+    );
+
+    let reference = BytesN::from_array(&env, &[7; 32]);
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &1, &8, &reference);
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employer, &contract_id, &1),
+        Err(Ok(LifecycleError::InvalidTimesheetState))
+    );
+
+    // A rejected timesheet can be corrected and resubmitted.
+    lifecycle_client.reject_timesheet(&employer, &contract_id, &1);
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &1, &6, &reference);
+    lifecycle_client.approve_timesheet(&employer, &contract_id, &1);
+    assert_eq!(lifecycle_client.pay_timesheet(&employer, &contract_id, &1), 150);
+    assert_eq!(token_client.balance(&employee), 150);
+    assert_eq!(lifecycle_client.get_timesheet(&contract_id, &1).unwrap().status, TimesheetStatus::Paid);
+
+    // The same period can be neither paid nor resubmitted again.
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employer, &contract_id, &1),
+        Err(Ok(LifecycleError::InvalidTimesheetState))
+    );
+    assert_eq!(
+        lifecycle_client.try_submit_timesheet(&employee, &contract_id, &1, &6, &reference),
+        Err(Ok(LifecycleError::InvalidTimesheetState))
+    );
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 150);
+}
+
+// What is still reserved for a contract and has not been paid out.
+fn unspent_reservation(vault_client: &VaultContractClient, contract_id: u32) -> i128 {
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    reservation.reserved - reservation.spent
+}
+
+#[test]
+fn test_timesheet_authorization_and_settlement_on_cancel() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let stranger = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_hourly_rate(&employer, &contract_id, &25);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    // Only the employee submits, and only the employer reviews and pays.
+    let reference = BytesN::from_array(&env, &[7; 32]);
+    for caller in [&employer, &stranger] {
+        assert_eq!(
+            lifecycle_client.try_submit_timesheet(caller, &contract_id, &1, &8, &reference),
+            Err(Ok(LifecycleError::NotAuthorized))
+        );
+    }
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &1, &8, &reference);
+    for caller in [&employee, &stranger] {
+        assert_eq!(
+            lifecycle_client.try_approve_timesheet(caller, &contract_id, &1),
+            Err(Ok(LifecycleError::NotAuthorized))
+        );
+    }
+    lifecycle_client.approve_timesheet(&employer, &contract_id, &1);
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employee, &contract_id, &1),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employer, &contract_id, &2),
+        Err(Ok(LifecycleError::TimesheetNotFound))
+    );
+    lifecycle_client.pay_timesheet(&employer, &contract_id, &1);
+
+    // Hours beyond what the contract has left are not paid.
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &2, &40, &reference);
+    lifecycle_client.approve_timesheet(&employer, &contract_id, &2);
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employer, &contract_id, &2),
+        Err(Ok(LifecycleError::InsufficientContractFunds))
+    );
+    assert_eq!(lifecycle_client.get_timesheet(&contract_id, &2).unwrap().status, TimesheetStatus::Approved);
+
+    // Cancelling settles the approved timesheet up to what the contract has
+    // left; a timesheet still under review is not paid.
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &3, &4, &reference);
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 200 + 800);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 1000);
+    assert_eq!(lifecycle_client.get_timesheet(&contract_id, &2).unwrap().status, TimesheetStatus::Paid);
+    assert_eq!(lifecycle_client.get_timesheet(&contract_id, &3).unwrap().status, TimesheetStatus::Submitted);
+    assert_eq!(
+        lifecycle_client.try_submit_timesheet(&employee, &contract_id, &4, &1, &reference),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(
+        lifecycle_client.try_pay_timesheet(&employer, &contract_id, &2),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+}
+
+#[test]
+fn test_usage_reports_payable_after_challenge_window() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let reporter = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_usage_terms(
+        &employer, &contract_id, &UsageTerms { reporter: reporter.clone(), unit_price: 3, challenge_window: 3_600 },
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let reference = BytesN::from_array(&env, &[1; 32]);
+    assert_eq!(
+        lifecycle_client.try_report_usage(&employer, &contract_id, &1, &100, &reference),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.report_usage(&reporter, &contract_id, &1, &100, &reference);
+    assert_eq!(
+        lifecycle_client.try_report_usage(&reporter, &contract_id, &1, &90, &reference),
+        Err(Ok(LifecycleError::DuplicateUsageReport))
+    );
+    assert_eq!(
+        lifecycle_client.try_pay_usage(&employee, &contract_id, &1),
+        Err(Ok(LifecycleError::ChallengeWindowOpen))
+    );
+
+    // The employer challenges; the reporter files a correction for the same period.
+    lifecycle_client.challenge_usage(&employer, &contract_id, &1);
+    assert_eq!(lifecycle_client.get_usage_report(&contract_id, &1).unwrap().status, UsageStatus::Challenged);
+    lifecycle_client.report_usage(&reporter, &contract_id, &1, &90, &reference);
+
+    env.ledger().with_mut(|li| li.timestamp = 3_600);
+    assert_eq!(
+        lifecycle_client.try_challenge_usage(&employer, &contract_id, &1),
+        Err(Ok(LifecycleError::ChallengeWindowClosed))
+    );
+    assert_eq!(lifecycle_client.pay_usage(&employee, &contract_id, &1), 270);
+    assert_eq!(token_client.balance(&employee), 270);
+    assert_eq!(
+        lifecycle_client.try_pay_usage(&employee, &contract_id, &1),
+        Err(Ok(LifecycleError::DuplicateUsageReport))
+    );
+}
+
+#[test]
+fn test_usage_authorization_and_settlement_on_cancel() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let reporter = Address::generate(&env);
+    let stranger = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    let terms = UsageTerms { reporter: reporter.clone(), unit_price: 3, challenge_window: 3_600 };
+    assert_eq!(
+        lifecycle_client.try_set_usage_terms(&employee, &contract_id, &terms),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    // Neither party may report its own usage.
+    assert_eq!(
+        lifecycle_client.try_set_usage_terms(
+            &employer, &contract_id, &UsageTerms { reporter: employee.clone(), unit_price: 3, challenge_window: 3_600 },
+        ),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.set_usage_terms(&employer, &contract_id, &terms);
+
+    let reference = BytesN::from_array(&env, &[1; 32]);
+    assert_eq!(
+        lifecycle_client.try_report_usage(&reporter, &contract_id, &1, &100, &reference),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_set_usage_terms(&employer, &contract_id, &terms),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    lifecycle_client.report_usage(&reporter, &contract_id, &1, &100, &reference);
+    lifecycle_client.report_usage(&reporter, &contract_id, &2, &400, &reference);
+    for caller in [&reporter, &stranger] {
+        assert_eq!(
+            lifecycle_client.try_challenge_usage(caller, &contract_id, &1),
+            Err(Ok(LifecycleError::NotAuthorized))
+        );
+    }
+
+    env.ledger().with_mut(|li| li.timestamp = 3_600);
+    assert_eq!(
+        lifecycle_client.try_pay_usage(&stranger, &contract_id, &1),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.pay_usage(&employer, &contract_id, &1);
+    // A paid report cannot be challenged or replaced any more.
+    assert_eq!(
+        lifecycle_client.try_challenge_usage(&employer, &contract_id, &1),
+        Err(Ok(LifecycleError::DuplicateUsageReport))
+    );
+    assert_eq!(
+        lifecycle_client.try_report_usage(&reporter, &contract_id, &1, &10, &reference),
+        Err(Ok(LifecycleError::DuplicateUsageReport))
+    );
+    // 400 units at 3 exceed the 700 left on the contract.
+    assert_eq!(
+        lifecycle_client.try_pay_usage(&employee, &contract_id, &2),
+        Err(Ok(LifecycleError::InsufficientContractFunds))
+    );
+
+    // Cancelling settles the unchallenged report up to what the contract has
+    // left; a report still open to challenge is not paid.
+    lifecycle_client.report_usage(&reporter, &contract_id, &3, &10, &reference);
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&employee), 300 + 700);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 1000);
+    assert_eq!(lifecycle_client.get_usage_report(&contract_id, &2).unwrap().status, UsageStatus::Paid);
+    assert_eq!(lifecycle_client.get_usage_report(&contract_id, &3).unwrap().status, UsageStatus::Pending);
+    assert_eq!(
+        lifecycle_client.try_report_usage(&reporter, &contract_id, &4, &1, &reference),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(
+        lifecycle_client.try_pay_usage(&employee, &contract_id, &2),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+}
+
+#[test]
+fn test_query_employer_contracts_paginates_and_filters() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let mut active = Vec::new(&env);
+    for i in 0..5 {
+        let contract_type = if i % 2 == 0 { ContractType::FixedRate } else { ContractType::PayAsYouGo };
+        let metadata = create_metadata(&employer, &employee, contract_type.clone(), 100, &token_address);
+        let id = lifecycle_client.create_contract(
+            &employer, &employee, &contract_type, &100, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+        );
+        if i < 3 {
+            lifecycle_client.accept_contract(&employee, &id);
+            active.push_back(id);
+        }
+    }
+
+    let any = ContractFilter { statuses: Vec::new(&env), contract_types: Vec::new(&env), assets: Vec::new(&env) };
+    let first = lifecycle_client.query_employer_contracts(&employer, &any, &0, &2);
+    assert_eq!(first.contracts.len(), 2);
+    assert_eq!(first.next_cursor, Some(2));
+    let second = lifecycle_client.query_employer_contracts(&employer, &any, &2, &10);
+    assert_eq!(second.contracts.len(), 3);
+    assert_eq!(second.next_cursor, None);
+
+    let active_fixed = ContractFilter {
+        statuses: Vec::from_array(&env, [ContractStatus::Active]),
+        contract_types: Vec::from_array(&env, [ContractType::FixedRate]),
+        assets: Vec::from_array(&env, [token_address.clone()]),
+    };
+    let page = lifecycle_client.query_employee_contracts(&employee, &active_fixed, &0, &10);
+    assert_eq!(page.contracts.len(), 2);
+    assert_eq!(page.contracts.get(0).unwrap().id, active.get(0).unwrap());
+    assert_eq!(page.contracts.get(1).unwrap().id, active.get(2).unwrap());
+
+    let other_asset = ContractFilter { assets: Vec::from_array(&env, [Address::generate(&env)]), ..any };
+    assert_eq!(lifecycle_client.query_employer_contracts(&employer, &other_asset, &0, &10).contracts.len(), 0);
+}
+
+#[test]
+fn test_contract_queries_stay_within_bounds_and_owner() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let other_employer = Address::generate(&env);
+
+    let own = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 100, &token_address,
+    );
+    let foreign = create_active_contract(
+        &lifecycle_client, &other_employer, &employee, ContractType::FixedRate, 100, &token_address,
+    );
+    let any = ContractFilter { statuses: Vec::new(&env), contract_types: Vec::new(&env), assets: Vec::new(&env) };
+
+    // An employer only ever sees its own contracts; the employee sees both.
+    let page = lifecycle_client.query_employer_contracts(&employer, &any, &0, &10);
+    assert_eq!(page.contracts.len(), 1);
+    assert_eq!(page.contracts.get(0).unwrap().id, own);
+    assert_eq!(lifecycle_client.query_employee_contracts(&employee, &any, &0, &10).contracts.len(), 2);
+    let page = lifecycle_client.query_employer_contracts(&other_employer, &any, &0, &10);
+    assert_eq!(page.contracts.get(0).unwrap().id, foreign);
+
+    // A cursor past the end yields an empty last page.
+    let page = lifecycle_client.query_employee_contracts(&employee, &any, &5, &10);
+    assert_eq!(page.contracts.len(), 0);
+    assert_eq!(page.next_cursor, None);
+
+    // A zero limit still makes progress one record at a time.
+    let page = lifecycle_client.query_employee_contracts(&employee, &any, &0, &0);
+    assert_eq!(page.contracts.len(), 1);
+    assert_eq!(page.next_cursor, Some(1));
+
+    // Unknown parties have no contracts.
+    let page = lifecycle_client.query_employer_contracts(&Address::generate(&env), &any, &0, &10);
+    assert_eq!(page.contracts.len(), 0);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn test_bump_contract_extends_records() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+
+    // Keep the instance alive so only the records run low on TTL.
+    env.as_contract(&lifecycle_client.address, || env.storage().instance().extend_ttl(200 * 17_280, 200 * 17_280));
+    env.ledger().with_mut(|li| li.sequence_number += 100 * 17_280);
+    lifecycle_client.bump_contract(&contract_id);
+
+    env.as_contract(&lifecycle_client.address, || {
+        assert!(!env.storage().instance().has(&DataKey::Contract(contract_id)));
+        assert_eq!(env.storage().persistent().get_ttl(&DataKey::Contract(contract_id)), RECORD_BUMP_AMOUNT);
+        assert_eq!(env.storage().persistent().get_ttl(&DataKey::EmployerContracts(employer.clone())), RECORD_BUMP_AMOUNT);
+    });
+}
+
+#[test]
+fn test_bump_contract_history_extends_payments_and_work_records() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let clerk = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_hourly_rate(&employer, &contract_id, &25);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let reference = BytesN::from_array(&env, &[7; 32]);
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &0, &8, &reference);
+    lifecycle_client.approve_timesheet(&employer, &contract_id, &0);
+    lifecycle_client.pay_timesheet(&employer, &contract_id, &0);
+    lifecycle_client.submit_timesheet(&employee, &contract_id, &1, &4, &reference);
+    assert_eq!(token_client.balance(&employee), 200);
+    env.as_contract(&lifecycle_client.address, || {
+        let key = DataKey::OperatorUsage(employer.clone(), clerk.clone());
+        env.storage().persistent().set(&key, &(0u64, 300i128));
+        env.storage().persistent().extend_ttl(&key, RECORD_BUMP_AMOUNT, RECORD_BUMP_AMOUNT);
+    });
+
+    env.as_contract(&lifecycle_client.address, || env.storage().instance().extend_ttl(200 * 17_280, 200 * 17_280));
+    env.ledger().with_mut(|li| li.sequence_number += 100 * 17_280);
+    let remaining = RECORD_BUMP_AMOUNT - 100 * 17_280;
+
+    // The contract bump covers the timesheet still open, but not settled history.
+    lifecycle_client.bump_contract(&contract_id);
+    env.as_contract(&lifecycle_client.address, || {
+        let storage = env.storage().persistent();
+        assert_eq!(storage.get_ttl(&DataKey::Timesheet(contract_id, 1)), RECORD_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&DataKey::OpenTimesheets(contract_id)), RECORD_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&DataKey::Timesheet(contract_id, 0)), remaining);
+        assert_eq!(storage.get_ttl(&DataKey::Payment(contract_id, 0)), remaining);
+    });
+
+    // History is extended a page at a time, by payment sequence and period.
+    assert_eq!(lifecycle_client.bump_contract_history(&contract_id, &0, &1_000), 200);
+    lifecycle_client.bump_operators(&employer, &Vec::from_array(&env, [clerk.clone()]));
+    env.as_contract(&lifecycle_client.address, || {
+        let storage = env.storage().persistent();
+        assert_eq!(storage.get_ttl(&DataKey::Timesheet(contract_id, 0)), RECORD_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&DataKey::Payment(contract_id, 0)), RECORD_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&DataKey::OperatorUsage(employer.clone(), clerk.clone())), RECORD_BUMP_AMOUNT);
+    });
+    assert_eq!(lifecycle_client.get_operator_usage(&employer, &clerk), 300);
+    assert_eq!(lifecycle_client.get_payment_history(&contract_id, &0, &10).len(), 1);
+
+    assert_eq!(lifecycle_client.try_bump_contract_history(&99, &0, &10), Err(Ok(LifecycleError::ContractNotFound)));
+    assert_eq!(lifecycle_client.try_bump_payroll_run(&99), Err(Ok(LifecycleError::PayrollRunNotFound)));
+}
+
+#[test]
+fn test_migrate_storage_converts_baseline_contracts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, _, _, token_address, _) = setup_contracts(&env, &admin, &employer, &employee);
+    let admin = lifecycle_client.get_admin();
+
+    // Recreate a deployment from before versioning: two contracts in the old
+    // shape, and the contract lists, all in instance storage.
+    let legacy = |id: u32, contract_type: ContractType, milestones: Option<Vec<Milestone>>| LegacyContract {
+        id,
+        employer: employer.clone(),
+        employee: employee.clone(),
+        contract_type: contract_type.clone(),
+        status: ContractStatus::Active,
+        total_amount: 1000,
+        paid_amount: 250,
+        asset: token_address.clone(),
+        metadata: create_metadata(&employer, &employee, contract_type, 1000, &token_address),
+        created_at: 500,
+        milestones,
+    };
+    let milestones = Vec::from_array(&env, [
+        Milestone { id: 1, description: String::from_str(&env, "P1"), amount: 1000, completed: false, completed_at: None },
+    ]);
+    env.as_contract(&lifecycle_client.address, || {
+        let storage = env.storage().instance();
+        storage.set(&DataKey::Contract(1), &legacy(1, ContractType::FixedRate, None));
+        storage.set(&DataKey::Contract(2), &legacy(2, ContractType::Milestone, Some(milestones)));
+        storage.set(&DataKey::NextContractId, &3u32);
+        storage.set(&DataKey::EmployerContracts(employer.clone()), &Vec::from_array(&env, [1u32, 2]));
+        storage.set(&DataKey::EmployeeContracts(employee.clone()), &Vec::from_array(&env, [1u32, 2]));
+        storage.remove(&UpgradeKey::SchemaVersion);
+    });
+    assert!(matches!(lifecycle_client.try_get_contract(&1), Err(Ok(LifecycleError::ContractNotFound))));
+
+    // Only the admin converts, and records cannot be bumped before they are.
+    assert_eq!(lifecycle_client.try_migrate_storage(&employer, &10), Err(Ok(LifecycleError::NotAuthorized)));
+    assert_eq!(lifecycle_client.try_bump_contract(&1), Err(Ok(LifecycleError::ContractNotFound)));
+
+    // The schema version cannot move past contracts that were never converted.
+    assert_eq!(lifecycle_client.try_migrate(), Err(Ok(LifecycleError::MigrationPending)));
+    assert_eq!(lifecycle_client.migrate_storage(&admin, &1), 1);
+    assert_eq!(lifecycle_client.try_migrate(), Err(Ok(LifecycleError::MigrationPending)));
+    assert_eq!(lifecycle_client.migrate_storage(&admin, &10), 1);
+    assert_eq!(lifecycle_client.migrate_storage(&admin, &10), 0);
+
+    let contract = lifecycle_client.get_contract(&1);
+    assert_eq!((contract.status, contract.paid_amount, contract.version), (ContractStatus::Active, 250, 1));
+    assert_eq!((contract.accepted_at, contract.start_date, contract.end_date), (Some(500), 500, None));
+    assert_eq!(lifecycle_client.get_contract(&2).milestones.unwrap().len(), 1);
+    assert_eq!(lifecycle_client.get_employer_contracts(&employer), Vec::from_array(&env, [1u32, 2]));
+    assert_eq!(lifecycle_client.get_employee_contracts(&employee), Vec::from_array(&env, [1u32, 2]));
+    env.as_contract(&lifecycle_client.address, || {
+        assert!(!env.storage().instance().has(&DataKey::Contract(1)));
+        assert!(!env.storage().instance().has(&DataKey::EmployerContracts(employer.clone())));
+    });
+
+    assert_eq!(lifecycle_client.migrate(), 1);
+    assert_eq!(lifecycle_client.version(), 1);
+    assert_eq!(lifecycle_client.try_migrate(), Err(Ok(LifecycleError::AlreadyMigrated)));
+}
+
+#[test]
+fn test_reserve_contract_funds_backfills_legacy_contracts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    // A live contract from before reservations existed, already partly paid.
+    env.as_contract(&lifecycle_client.address, || {
+        let legacy = LegacyContract {
+            id: 1,
+            employer: employer.clone(),
+            employee: employee.clone(),
+            contract_type: ContractType::FixedRate,
+            status: ContractStatus::Active,
+            total_amount: 1000,
+            paid_amount: 250,
+            asset: token_address.clone(),
+            metadata: create_metadata(&employer, &employee, ContractType::FixedRate, 1000, &token_address),
+            created_at: 0,
+            milestones: None,
+        };
+        env.storage().instance().set(&DataKey::Contract(1), &legacy);
+        env.storage().instance().set(&DataKey::NextContractId, &2u32);
+    });
+    lifecycle_client.migrate_storage(&lifecycle_client.get_admin(), &10);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &1, &100, &None),
+        Err(Ok(LifecycleError::VaultPaymentFailed))
+    );
+
+    assert_eq!(
+        lifecycle_client.try_reserve_contract_funds(&employee, &1),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(lifecycle_client.reserve_contract_funds(&employer, &1), 750);
+    assert_eq!(vault_client.get_reservation(&1).unwrap().reserved, 750);
+    assert_eq!(lifecycle_client.reserve_contract_funds(&lifecycle_client.get_admin(), &1), 0);
+
+    lifecycle_client.process_fixed_payment(&employer, &1, &750, &None);
+    assert_eq!(token_client.balance(&employee), 750);
+    assert_eq!(lifecycle_client.get_contract(&1).status, ContractStatus::Completed);
+    assert_eq!(
+        lifecycle_client.try_reserve_contract_funds(&employer, &1),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+}
+
+#[test]
+fn test_migrate_runs_once_per_version() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, _, _, _, _) = setup_contracts(&env, &admin, &employer, &employee);
+
+    assert_eq!(lifecycle_client.version(), 1);
+    assert_eq!(lifecycle_client.try_migrate(), Err(Ok(LifecycleError::AlreadyMigrated)));
+
+    env.as_contract(&lifecycle_client.address, || env.storage().instance().remove(&UpgradeKey::SchemaVersion));
+    assert_eq!(lifecycle_client.version(), 0);
+    assert_eq!(lifecycle_client.migrate(), 1);
+    assert_eq!(lifecycle_client.version(), 1);
+}
+
+#[test]
+fn test_payment_history_records_each_payout() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+
+    let memo = String::from_str(&env, "INV-001");
+    env.ledger().with_mut(|li| li.timestamp = 500);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &300, &Some(memo.clone()));
+    env.ledger().with_mut(|li| li.timestamp = 900);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &200, &None);
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 2);
+
+    let history = lifecycle_client.get_payment_history(&contract_id, &0, &10);
+    assert_eq!(history.len(), 2);
+    let first = history.get(0).unwrap();
+    assert_eq!(first.seq, 0);
+    assert_eq!(first.amount, 300);
+    assert_eq!((first.deducted, first.net), (0, 300));
+    assert_eq!(first.asset, token_address);
+    assert_eq!(first.timestamp, 500);
+    assert_eq!(first.kind, PaymentKind::Fixed);
+    assert_eq!(first.milestone_id, None);
+    assert_eq!(first.memo, Some(memo));
+
+    let page = lifecycle_client.get_payment_history(&contract_id, &1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().amount, 200);
+    assert_eq!(lifecycle_client.get_payment_history(&contract_id, &2, &10).len(), 0);
+}
+
+#[test]
+fn test_payment_history_ignores_failed_payouts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+
+    // Rejected payouts leave no record behind.
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employee, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &1001, &None),
+        Err(Ok(LifecycleError::InsufficientContractFunds))
+    );
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 0);
+
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &1000, &None);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &1, &None),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 1);
+    assert_eq!(lifecycle_client.get_payment_history(&contract_id, &0, &10).len(), 1);
+
+    // Unknown contracts have an empty history.
+    assert_eq!(lifecycle_client.get_payment_count(&99), 0);
+    assert_eq!(lifecycle_client.get_payment_history(&99, &0, &10).len(), 0);
+}
+
+fn register_worker(env: &Env, profile_id: &Address) -> Address {
+    let profile_client = ProfileContractClient::new(env, profile_id);
+    let worker = Address::generate(env);
+    profile_client.create_profile(&worker, &String::from_str(env, "Worker"), &false);
+    let wallet = String::from_str(env, "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF");
+    profile_client.register_worker_wallet(&worker, &wallet);
+    worker
+}
+
+#[test]
+fn test_team_contract_splits_payouts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, profile_id, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let lead = register_worker(&env, &profile_id);
+    let dev = register_worker(&env, &profile_id);
+    let designer = register_worker(&env, &profile_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 2000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &2000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+
+    let uneven = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Percent(6_000) },
+        Payee { address: dev.clone(), rule: SplitRule::Percent(3_000) },
+    ]);
+    assert_eq!(
+        lifecycle_client.try_set_payees(&employer, &contract_id, &uneven),
+        Err(Ok(LifecycleError::InvalidSplitRule))
+    );
+    let unregistered = Vec::from_array(&env, [
+        Payee { address: Address::generate(&env), rule: SplitRule::Percent(10_000) },
+    ]);
+    assert_eq!(
+        lifecycle_client.try_set_payees(&employer, &contract_id, &unregistered),
+        Err(Ok(LifecycleError::EmployeeProfileNotFound))
+    );
+
+    let payees = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Percent(6_000) },
+        Payee { address: dev.clone(), rule: SplitRule::Percent(4_000) },
+        Payee { address: designer.clone(), rule: SplitRule::Fixed(50) },
+    ]);
+    lifecycle_client.set_payees(&employer, &contract_id, &payees);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_payees(&contract_id), payees);
+
+    // The fixed share comes off the top; percentages split what is left.
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &1000, &None);
+    assert_eq!(token_client.balance(&designer), 50);
+    assert_eq!(token_client.balance(&lead), 570);
+    assert_eq!(token_client.balance(&dev), 380);
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().spent, 1000);
+
+    // Too small to cover the fixed share: nothing is paid.
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &40, &None),
+        Err(Ok(LifecycleError::InvalidSplitRule))
+    );
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 1000);
+}
+
+#[test]
+fn test_team_payees_share_payouts_until_cancelled() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, profile_id, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let lead = register_worker(&env, &profile_id);
+    let dev = register_worker(&env, &profile_id);
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+
+    let payees = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Percent(5_000) },
+        Payee { address: dev.clone(), rule: SplitRule::Percent(5_000) },
+    ]);
+    assert_eq!(
+        lifecycle_client.try_set_payees(&employee, &contract_id, &payees),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    let duplicated = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Percent(5_000) },
+        Payee { address: lead.clone(), rule: SplitRule::Percent(5_000) },
+    ]);
+    let overdrawn = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Fixed(1_001) },
+    ]);
+    let empty_share = Vec::from_array(&env, [
+        Payee { address: lead.clone(), rule: SplitRule::Percent(10_000) },
+        Payee { address: dev.clone(), rule: SplitRule::Fixed(0) },
+    ]);
+    for invalid in [duplicated, overdrawn, empty_share] {
+        assert_eq!(
+            lifecycle_client.try_set_payees(&employer, &contract_id, &invalid),
+            Err(Ok(LifecycleError::InvalidSplitRule))
+        );
+    }
+    assert_eq!(lifecycle_client.get_payees(&contract_id).len(), 0);
+
+    // The split is part of the offer and fixed once it is accepted.
+    lifecycle_client.set_payees(&employer, &contract_id, &payees);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_set_payees(&employer, &contract_id, &Vec::new(&env)),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    // Each payout is shared by the payees; the named employee receives nothing.
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &400, &None);
+    assert_eq!((token_client.balance(&lead), token_client.balance(&dev)), (200, 200));
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 400);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 600);
+
+    // Cancelling a team contract returns everything left to the vault, and
+    // nobody can be paid from it afterwards.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Cancelled);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!((token_client.balance(&lead), token_client.balance(&dev)), (200, 200));
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 9_600);
+}
+
+#[test]
+fn test_deductions_withheld_from_payouts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let tax_office = Address::generate(&env);
+    let pension_fund = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 2000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &2000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+
+    let excessive = Vec::from_array(&env, [
+        Deduction { name: String::from_str(&env, "Tax"), recipient: tax_office.clone(), rule: SplitRule::Percent(8_000) },
+        Deduction { name: String::from_str(&env, "Pension"), recipient: pension_fund.clone(), rule: SplitRule::Percent(3_000) },
+    ]);
+    assert_eq!(
+        lifecycle_client.try_set_deductions(&employer, &contract_id, &excessive),
+        Err(Ok(LifecycleError::InvalidSplitRule))
+    );
+
+    let deductions = Vec::from_array(&env, [
+        Deduction { name: String::from_str(&env, "Tax"), recipient: tax_office.clone(), rule: SplitRule::Percent(2_000) },
+        Deduction { name: String::from_str(&env, "Pension"), recipient: pension_fund.clone(), rule: SplitRule::Fixed(50) },
+    ]);
+    lifecycle_client.set_deductions(&employer, &contract_id, &deductions);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_deductions(&contract_id), deductions);
+
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &1000, &None);
+    assert_eq!(token_client.balance(&tax_office), 200);
+    assert_eq!(token_client.balance(&pension_fund), 50);
+    assert_eq!(token_client.balance(&employee), 750);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().spent, 1000);
+
+    let record = lifecycle_client.get_payment_history(&contract_id, &0, &1).get(0).unwrap();
+    assert_eq!((record.amount, record.deducted, record.net), (1000, 250, 750));
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 1000);
+
+    // The fixed deduction exceeds a small payout: nothing is paid.
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&employer, &contract_id, &40, &None),
+        Err(Ok(LifecycleError::InvalidSplitRule))
+    );
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 1000);
+}
+
+#[test]
+fn test_deductions_fixed_at_acceptance_and_withheld_from_settlement() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let tax_office = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    let deduction = |rule: SplitRule| Deduction { name: String::from_str(&env, "Tax"), recipient: tax_office.clone(), rule };
+    let deductions = Vec::from_array(&env, [deduction(SplitRule::Percent(1_000))]);
+
+    // The employee cannot set, and so cannot strip, their own deductions.
+    assert_eq!(
+        lifecycle_client.try_set_deductions(&employee, &contract_id, &deductions),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    for invalid in [SplitRule::Percent(0), SplitRule::Fixed(0)] {
+        assert_eq!(
+            lifecycle_client.try_set_deductions(&employer, &contract_id, &Vec::from_array(&env, [deduction(invalid)])),
+            Err(Ok(LifecycleError::InvalidSplitRule))
+        );
+    }
+
+    lifecycle_client.set_deductions(&employer, &contract_id, &deductions);
+    lifecycle_client.set_termination_terms(&employer, &contract_id, &TerminationTerms { notice_period: 0, severance: 500 });
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_set_deductions(&employer, &contract_id, &Vec::new(&env)),
+        Err(Ok(LifecycleError::ContractNotDraft))
+    );
+
+    // A settlement is withheld from like any payout, and nothing stays reserved.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(token_client.balance(&tax_office), 50);
+    assert_eq!(token_client.balance(&employee), 450);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+
+    let contract = lifecycle_client.get_contract(&contract_id);
+    assert_eq!((contract.status, contract.paid_amount), (ContractStatus::Cancelled, 500));
+    let record = lifecycle_client.get_payment_history(&contract_id, &0, &10).get(0).unwrap();
+    assert_eq!(record.kind, PaymentKind::Settlement);
+    assert_eq!((record.amount, record.deducted, record.net), (500, 50, 450));
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 1);
+}
+
+#[test]
+fn test_payslip_reconciles_with_balances_under_fees() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    // 1%, but at least 5 per payout.
+    vault_client.set_fee_config(&vault_client.get_admin(), &token_address, &FeeConfig { bps: 100, min_fee: 5 });
+
+    let tax_office = Address::generate(&env);
+    let pension_fund = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 2000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &2000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_deductions(&employer, &contract_id, &Vec::from_array(&env, [
+        Deduction { name: String::from_str(&env, "Tax"), recipient: tax_office.clone(), rule: SplitRule::Percent(2_000) },
+        Deduction { name: String::from_str(&env, "Pension"), recipient: pension_fund.clone(), rule: SplitRule::Fixed(50) },
+    ]));
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 8000);
+
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &1000, &None);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &500, &None);
+
+    let history = lifecycle_client.get_payment_history(&contract_id, &0, &10);
+    let first = history.get(0).unwrap();
+    let second = history.get(1).unwrap();
+    assert_eq!((first.amount, first.deducted, first.net, first.fee), (1000, 250, 740, 10));
+    assert_eq!((second.amount, second.deducted, second.net, second.fee), (500, 150, 345, 5));
+
+    // Every figure on the payslips matches what actually moved.
+    let remitted = token_client.balance(&tax_office) + token_client.balance(&pension_fund);
+    assert_eq!(token_client.balance(&employee), first.net + second.net);
+    assert_eq!(remitted, first.deducted + second.deducted);
+    assert_eq!(token_client.balance(&vault_id), 10_000 - first.amount - second.amount + first.fee + second.fee);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().spent, first.amount + second.amount);
+
+    // The fee was charged once per payout and withheld from the employee's
+    // net, so unallocated liquidity was never touched.
+    let stats = vault_client.get_treasury_stats(&token_address);
+    assert_eq!(stats.total_fees, first.fee + second.fee);
+    assert_eq!(stats.total_liquidity, 8000);
+}
+
+#[test]
+fn test_fees_do_not_block_payouts_from_a_fully_reserved_vault() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 1000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 0);
+
+    vault_client.set_fee_config(&vault_client.get_admin(), &token_address, &FeeConfig { bps: 100, min_fee: 0 });
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &600, &None);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &400, &None);
+
+    // Both payouts came out of the reservation alone, fee included.
+    assert_eq!(token_client.balance(&employee), 990);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Completed);
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (1000, 1000));
+    let stats = vault_client.get_treasury_stats(&token_address);
+    assert_eq!((stats.total_fees, stats.total_liquidity), (10, 0));
+}
+
+#[test]
+fn test_expense_claims_reimbursed_outside_contract_total() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+
+    let manager = Address::generate(&env);
+    let receipt = BytesN::from_array(&env, &[7; 32]);
+    let travel = lifecycle_client.submit_claim(&employee, &contract_id, &250, &symbol_short!("travel"), &receipt);
+    let laptop = lifecycle_client.submit_claim(&employee, &contract_id, &900, &symbol_short!("equipment"), &receipt);
+    assert_eq!(lifecycle_client.get_claim_count(&contract_id), 2);
+
+    // Only the employer and their delegated approvers may review claims.
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&manager, &contract_id, &travel),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.set_claim_approver(&employer, &manager, &true);
+    lifecycle_client.approve_claim(&manager, &contract_id, &travel);
+    lifecycle_client.reject_claim(&employer, &contract_id, &laptop);
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employer, &contract_id, &laptop),
+        Err(Ok(LifecycleError::InvalidClaimState))
+    );
+
+    assert_eq!(token_client.balance(&employee), 250);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 0);
+    assert_eq!(lifecycle_client.get_claim(&contract_id, &laptop).unwrap().status, ClaimStatus::Rejected);
+
+    let history = lifecycle_client.get_claim_history(&contract_id, &travel);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get(0).unwrap().status, ClaimStatus::Submitted);
+    assert_eq!(history.get(1).unwrap().status, ClaimStatus::Approved);
+    assert_eq!(history.get(1).unwrap().actor, manager);
+    assert_eq!(history.get(2).unwrap().status, ClaimStatus::Paid);
+}
+
+#[test]
+fn test_claims_on_closed_contracts_are_not_paid() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 2_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let receipt = BytesN::from_array(&env, &[7; 32]);
+    let travel = lifecycle_client.submit_claim(&employee, &contract_id, &100, &symbol_short!("travel"), &receipt);
+    let hotel = lifecycle_client.submit_claim(&employee, &contract_id, &200, &symbol_short!("travel"), &receipt);
+
+    // A claim filed while the contract ran is not paid once it has closed,
+    // but can still be turned down.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employer, &contract_id, &travel),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(lifecycle_client.get_claim(&contract_id, &travel).unwrap().status, ClaimStatus::Submitted);
+    lifecycle_client.reject_claim(&employer, &contract_id, &hotel);
+    assert_eq!(lifecycle_client.get_claim(&contract_id, &hotel).unwrap().status, ClaimStatus::Rejected);
+
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 2_000);
+}
+
+#[test]
+fn test_expense_claims_paid_once_beside_the_contract_reservation() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 2_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let other_employer = Address::generate(&env);
+    let manager = Address::generate(&env);
+    let receipt = BytesN::from_array(&env, &[7; 32]);
+
+    assert_eq!(
+        lifecycle_client.try_submit_claim(&employer, &contract_id, &100, &symbol_short!("travel"), &receipt),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_submit_claim(&employee, &contract_id, &0, &symbol_short!("travel"), &receipt),
+        Err(Ok(LifecycleError::InvalidAmount))
+    );
+    let travel = lifecycle_client.submit_claim(&employee, &contract_id, &100, &symbol_short!("travel"), &receipt);
+
+    // Employees cannot approve their own claims, and approvers only act for
+    // the employer that appointed them, until they are removed.
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employee, &contract_id, &travel),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.set_claim_approver(&other_employer, &manager, &true);
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&manager, &contract_id, &travel),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.set_claim_approver(&employer, &manager, &true);
+    lifecycle_client.set_claim_approver(&employer, &manager, &false);
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&manager, &contract_id, &travel),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employer, &contract_id, &7),
+        Err(Ok(LifecycleError::ClaimNotFound))
+    );
+
+    // Approval pays the claim at once, on top of the contract's pay.
+    lifecycle_client.approve_claim(&employer, &contract_id, &travel);
+    let claim = lifecycle_client.get_claim(&contract_id, &travel).unwrap();
+    assert_eq!(claim.status, ClaimStatus::Paid);
+    let mut statuses = Vec::new(&env);
+    for change in claim.history.iter() {
+        statuses.push_back(change.status);
+    }
+    assert_eq!(statuses, Vec::from_array(&env, [ClaimStatus::Submitted, ClaimStatus::Approved, ClaimStatus::Paid]));
+    assert_eq!(claim.history.get(2).unwrap().actor, employer);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 0);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 900);
+
+    // A paid claim is never paid again.
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employer, &contract_id, &travel),
+        Err(Ok(LifecycleError::InvalidClaimState))
+    );
+    assert_eq!(
+        lifecycle_client.try_reject_claim(&employer, &contract_id, &travel),
+        Err(Ok(LifecycleError::InvalidClaimState))
+    );
+    assert_eq!(token_client.balance(&employee), 100);
+
+    // Without the liquidity to cover it, a claim stays submitted.
+    let laptop = lifecycle_client.submit_claim(&employee, &contract_id, &1_500, &symbol_short!("equipment"), &receipt);
+    assert_eq!(
+        lifecycle_client.try_approve_claim(&employer, &contract_id, &laptop),
+        Err(Ok(LifecycleError::InsufficientVaultFunds))
+    );
+    assert_eq!(lifecycle_client.get_claim(&contract_id, &laptop).unwrap().status, ClaimStatus::Submitted);
+    assert_eq!(token_client.balance(&employee), 100);
+
+    // Reimbursements never touch what is reserved for the contract's pay.
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 1000);
+    assert_eq!(
+        lifecycle_client.try_submit_claim(&employee, &99, &100, &symbol_short!("travel"), &receipt),
+        Err(Ok(LifecycleError::ContractNotFound))
+    );
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    assert_eq!(
+        lifecycle_client.try_submit_claim(&employee, &contract_id, &100, &symbol_short!("travel"), &receipt),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 1_900);
+    assert_eq!(token_client.balance(&employee), 100);
+}
+
+#[test]
+fn test_salary_advance_recovered_from_later_payouts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+
+    assert_eq!(
+        lifecycle_client.try_request_advance(&employee, &contract_id, &100),
+        Err(Ok(LifecycleError::AdvanceLimitExceeded))
+    );
+    lifecycle_client.set_advance_policy(
+        &employer, &contract_id, &AdvancePolicy { max_share_bps: 5_000, auto_approve_limit: 100 },
+    );
+    assert_eq!(
+        lifecycle_client.try_request_advance(&employee, &contract_id, &501),
+        Err(Ok(LifecycleError::AdvanceLimitExceeded))
+    );
+
+    // Small advances are paid straight away; only one may be open at a time.
+    let first = lifecycle_client.request_advance(&employee, &contract_id, &80);
+    assert_eq!(token_client.balance(&employee), 80);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 80);
+    assert_eq!(
+        lifecycle_client.try_request_advance(&employee, &contract_id, &10),
+        Err(Ok(LifecycleError::AdvanceLimitExceeded))
+    );
+
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &50, &None);
+    assert_eq!(token_client.balance(&employee), 80);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &200, &None);
+    assert_eq!(token_client.balance(&employee), 250);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 250);
+    assert_eq!(lifecycle_client.get_advance(&contract_id, &first).unwrap().status, AdvanceStatus::Repaid);
+
+    let record = lifecycle_client.get_payment_history(&contract_id, &2, &1).get(0).unwrap();
+    assert_eq!((record.amount, record.deducted, record.net), (200, 30, 170));
+
+    // Larger advances wait for the employer.
+    let second = lifecycle_client.request_advance(&employee, &contract_id, &300);
+    assert_eq!(token_client.balance(&employee), 250);
+    lifecycle_client.approve_advance(&employer, &contract_id, &second);
+    assert_eq!(token_client.balance(&employee), 550);
+    assert_eq!(
+        lifecycle_client.try_approve_advance(&employer, &contract_id, &second),
+        Err(Ok(LifecycleError::NoPendingAdvance))
+    );
+
+    // Cancelling writes off what the settlement could not recover.
+    lifecycle_client.cancel_contract(&employer, &contract_id);
+    let advances = lifecycle_client.get_advances(&contract_id);
+    assert_eq!(advances.len(), 2);
+    assert_eq!(advances.get(1).unwrap().status, AdvanceStatus::WrittenOff);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 0);
+
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (550, 550));
+}
+
+#[test]
+fn test_advance_and_its_recovery_are_taxed_once() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let tax_office = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::PayAsYouGo, 1000, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::PayAsYouGo, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_deductions(&employer, &contract_id, &Vec::from_array(&env, [
+        Deduction { name: String::from_str(&env, "Tax"), recipient: tax_office.clone(), rule: SplitRule::Percent(2_000) },
+    ]));
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    lifecycle_client.set_advance_policy(
+        &employer, &contract_id, &AdvancePolicy { max_share_bps: 5_000, auto_approve_limit: 300 },
+    );
+
+    // The advance is taxed as it is paid out.
+    lifecycle_client.request_advance(&employee, &contract_id, &300);
+    assert_eq!(token_client.balance(&tax_office), 60);
+    assert_eq!(token_client.balance(&employee), 240);
+
+    // The payout that recovers it is not taxed a second time; the next is.
+    lifecycle_client.process_payg_payment(&employer, &contract_id, &300, &None);
+    assert_eq!(token_client.balance(&tax_office), 60);
+    assert_eq!(token_client.balance(&employee), 240);
+    lifecycle_client.process_payg_payment(&employer, &contract_id, &300, &None);
+
+    // 600 earned in total, 20% of it withheld.
+    assert_eq!(token_client.balance(&tax_office), 120);
+    assert_eq!(token_client.balance(&employee), 480);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 600);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 0);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().spent, 600);
+
+    let history = lifecycle_client.get_payment_history(&contract_id, &0, &10);
+    let advance = history.get(0).unwrap();
+    assert_eq!(advance.kind, PaymentKind::Advance);
+    assert_eq!((advance.amount, advance.deducted, advance.net), (300, 60, 240));
+    let recovery = history.get(1).unwrap();
+    assert_eq!((recovery.amount, recovery.deducted, recovery.net), (300, 300, 0));
+}
+
+#[test]
+fn test_salary_advances_follow_the_contract_to_resignation() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let stream_metadata = create_metadata(&employer, &employee, ContractType::Streaming, 1000, &token_address);
+    let stream_id = lifecycle_client.create_stream(
+        &employer, &employee, &1000, &token_address, &stream_metadata, &0, &10_000, &OFFER_EXPIRY,
+    );
+
+    let policy = AdvancePolicy { max_share_bps: 5_000, auto_approve_limit: 0 };
+    assert_eq!(
+        lifecycle_client.try_set_advance_policy(&employee, &contract_id, &policy),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_set_advance_policy(&employer, &stream_id, &policy),
+        Err(Ok(LifecycleError::InvalidContractType))
+    );
+    assert_eq!(
+        lifecycle_client.try_set_advance_policy(
+            &employer, &contract_id, &AdvancePolicy { max_share_bps: 10_001, auto_approve_limit: 0 },
+        ),
+        Err(Ok(LifecycleError::InvalidAmount))
+    );
+    lifecycle_client.set_advance_policy(&employer, &contract_id, &policy);
+
+    // Only the employee asks, and only the employer decides.
+    assert_eq!(
+        lifecycle_client.try_request_advance(&employer, &contract_id, &100),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    let rejected = lifecycle_client.request_advance(&employee, &contract_id, &100);
+    assert_eq!(
+        lifecycle_client.try_approve_advance(&employee, &contract_id, &rejected),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_reject_advance(&employee, &contract_id, &rejected),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.reject_advance(&employer, &contract_id, &rejected);
+    assert_eq!(
+        lifecycle_client.try_approve_advance(&employer, &contract_id, &rejected),
+        Err(Ok(LifecycleError::NoPendingAdvance))
+    );
+    assert_eq!(lifecycle_client.get_advance(&contract_id, &rejected).unwrap().status, AdvanceStatus::Rejected);
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 1000);
+
+    // An approved advance is paid out of the reservation and won back from
+    // the next payout.
+    let approved = lifecycle_client.request_advance(&employee, &contract_id, &300);
+    lifecycle_client.approve_advance(&employer, &contract_id, &approved);
+    assert_eq!(token_client.balance(&employee), 300);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 300);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 700);
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &400, &None);
+    assert_eq!(token_client.balance(&employee), 400);
+    assert_eq!(lifecycle_client.get_advance(&contract_id, &approved).unwrap().status, AdvanceStatus::Repaid);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 0);
+
+    // A request still waiting when the employee resigns is never paid.
+    let pending = lifecycle_client.request_advance(&employee, &contract_id, &200);
+    lifecycle_client.resign(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_advance(&contract_id, &pending).unwrap().status, AdvanceStatus::Rejected);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Cancelled);
+    assert_eq!(
+        lifecycle_client.try_approve_advance(&employer, &contract_id, &pending),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(
+        lifecycle_client.try_request_advance(&employee, &contract_id, &100),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(token_client.balance(&employee), 400);
+    assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 0);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    // Only the streaming offer is still reserved.
+    assert_eq!(vault_client.get_total_reserved(&token_address), 1000);
+}
+
+#[test]
+fn test_delegated_operator_runs_payroll_within_cap() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let clerk = Address::generate(&env);
+    let identity_id = env.register(IdentityManagementContract, ());
+    let identity_client = IdentityManagementContractClient::new(&env, &identity_id);
+    identity_client.initialize(&admin);
+    lifecycle_client.set_identity_contract(&lifecycle_client.get_admin(), &identity_id);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 1000, &token_address);
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &clerk, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+        ).map(|_| ()),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    let scopes = Vec::from_array(&env, [OperatorScope::CreateContracts, OperatorScope::ProcessPayments]);
+    identity_client.grant_operator(&employer, &clerk, &scopes, &Some(1500), &Some(10_000));
+
+    // A delegation from one employer does not let the clerk create offers for another.
+    let other_employer = Address::generate(&env);
+    let other_metadata = create_metadata(&other_employer, &employee, ContractType::FixedRate, 1000, &token_address);
+    assert_eq!(
+        lifecycle_client.try_create_contract(
+            &clerk, &employee, &ContractType::FixedRate, &1000, &token_address, &other_metadata, &None, &None,
+            &OFFER_EXPIRY,
+        ).map(|_| ()),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // The clerk creates the offer, but it belongs to the employer.
+    let contract_id = lifecycle_client.create_contract(
+        &clerk, &employee, &ContractType::FixedRate, &1000, &token_address, &metadata, &None, &None, &OFFER_EXPIRY,
+    );
+    assert_eq!(lifecycle_client.get_contract(&contract_id).employer, employer);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    lifecycle_client.process_fixed_payment(&clerk, &contract_id, &400, &None);
+    assert_eq!(token_client.balance(&employee), 400);
+    assert_eq!(lifecycle_client.get_operator_usage(&employer, &clerk), 1400);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&clerk, &contract_id, &200, &None),
+        Err(Ok(LifecycleError::OperatorCapExceeded))
+    );
+    assert_eq!(
+        lifecycle_client.try_complete_milestone(&clerk, &contract_id, &1),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // The employer is never capped, and delegations lapse at their expiry.
+    lifecycle_client.process_fixed_payment(&employer, &contract_id, &200, &None);
+    identity_client.grant_operator(&employer, &clerk, &scopes, &None, &Some(10_000));
+    lifecycle_client.process_fixed_payment(&clerk, &contract_id, &100, &None);
+    env.ledger().with_mut(|li| li.timestamp = 10_000);
+    assert_eq!(
+        lifecycle_client.try_process_fixed_payment(&clerk, &contract_id, &100, &None),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(token_client.balance(&employee), 700);
+}
+
+#[test]
+fn test_run_payroll_pays_many_contracts() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let fixed = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let payg = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1000, &token_address,
+    );
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let scheduled = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.accept_contract(&employee, &scheduled);
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 15 * 86_400);
+
+    let item = |contract_id: u32, amount: PayrollAmount| PayrollItem { contract_id, amount };
+    let items = Vec::from_array(&env, [
+        item(fixed, PayrollAmount::Amount(300)),
+        item(payg, PayrollAmount::DuePeriods),
+        item(payg, PayrollAmount::Amount(200)),
+        item(99, PayrollAmount::Amount(100)),
+        item(scheduled, PayrollAmount::DuePeriods),
+    ]);
+
+    // All-or-nothing stops at the first bad item and pays nobody.
+    assert_eq!(
+        lifecycle_client.try_run_payroll(&employer, &items, &PayrollMode::AllOrNothing),
+        Err(Ok(LifecycleError::InvalidContractType))
+    );
+    assert_eq!(token_client.balance(&employee), 0);
+
+    let report = lifecycle_client.run_payroll(&employer, &items, &PayrollMode::BestEffort);
+    assert_eq!(report.run_id, 1);
+    assert_eq!(report.outcomes.len(), 5);
+    assert_eq!(report.outcomes.get(0).unwrap().paid, 300);
+    assert_eq!(report.outcomes.get(1).unwrap().skipped, Some(LifecycleError::InvalidContractType as u32));
+    assert_eq!(report.outcomes.get(2).unwrap().paid, 200);
+    assert_eq!(report.outcomes.get(3).unwrap().skipped, Some(LifecycleError::ContractNotFound as u32));
+    assert_eq!(report.outcomes.get(4).unwrap().paid, 200);
+
+    assert_eq!(token_client.balance(&employee), 700);
+    assert_eq!(lifecycle_client.get_contract(&fixed).paid_amount, 300);
+    assert_eq!(lifecycle_client.get_payment_schedule(&scheduled).unwrap().periods_paid, 2);
+    assert_eq!(vault_client.get_reservation(&payg).unwrap().spent, 200);
+    assert_eq!(lifecycle_client.get_payment_count(&fixed), 1);
+
+    // Nothing is due yet, so the schedule is skipped the second time.
+    let report = lifecycle_client.run_payroll(
+        &employer, &Vec::from_array(&env, [item(scheduled, PayrollAmount::DuePeriods)]), &PayrollMode::BestEffort,
+    );
+    assert_eq!(report.run_id, 2);
+    assert_eq!(report.outcomes.get(0).unwrap().skipped, Some(LifecycleError::NoPaymentDue as u32));
+}
+
+#[test]
+fn test_run_payroll_pays_each_contract_within_its_total() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 500, &token_address,
+    );
+    let stranger = Address::generate(&env);
+    let item = |amount: i128| PayrollItem { contract_id, amount: PayrollAmount::Amount(amount) };
+
+    assert_eq!(
+        lifecycle_client.try_run_payroll(&employer, &Vec::new(&env), &PayrollMode::BestEffort),
+        Err(Ok(LifecycleError::InvalidPayrollRun))
+    );
+
+    // Someone else's contracts are skipped, or fail the run outright.
+    let items = Vec::from_array(&env, [item(100)]);
+    assert_eq!(
+        lifecycle_client.try_run_payroll(&stranger, &items, &PayrollMode::AllOrNothing),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    let report = lifecycle_client.run_payroll(&stranger, &items, &PayrollMode::BestEffort);
+    assert_eq!(report.outcomes.get(0).unwrap().skipped, Some(LifecycleError::NotAuthorized as u32));
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 0);
+
+    // Listing a contract twice cannot pay it beyond its total.
+    let items = Vec::from_array(&env, [item(300), item(300)]);
+    let report = lifecycle_client.run_payroll(&employer, &items, &PayrollMode::BestEffort);
+    assert_eq!(report.outcomes.get(0).unwrap().paid, 300);
+    assert_eq!(report.outcomes.get(1).unwrap().skipped, Some(LifecycleError::InsufficientContractFunds as u32));
+    assert_eq!(token_client.balance(&employee), 300);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 300);
+    assert_eq!(lifecycle_client.get_payment_count(&contract_id), 1);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 200);
+
+    // Paying out the rest completes the contract, and later runs skip it.
+    let report = lifecycle_client.run_payroll(&employer, &Vec::from_array(&env, [item(200)]), &PayrollMode::AllOrNothing);
+    assert_eq!(report.outcomes.get(0).unwrap().paid, 200);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Completed);
+    let report = lifecycle_client.run_payroll(&employer, &Vec::from_array(&env, [item(100)]), &PayrollMode::BestEffort);
+    assert_eq!(report.outcomes.get(0).unwrap().skipped, Some(LifecycleError::ContractNotActive as u32));
+    assert_eq!(token_client.balance(&employee), 500);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+}
+
+#[test]
+fn test_best_effort_payroll_skips_items_without_side_effects() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let clerk = Address::generate(&env);
+    let identity_id = env.register(IdentityManagementContract, ());
+    let identity_client = IdentityManagementContractClient::new(&env, &identity_id);
+    identity_client.initialize(&admin);
+    lifecycle_client.set_identity_contract(&lifecycle_client.get_admin(), &identity_id);
+    let scopes = Vec::from_array(&env, [OperatorScope::ProcessPayments]);
+    identity_client.grant_operator(&employer, &clerk, &scopes, &Some(800), &None);
+
+    // An underfunded contract, as left by a deployment from before reservations.
+    let short = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    vault_client.release_reserved(&short, &900);
+    let keeper = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    lifecycle_client.accept_contract(&employee, &keeper);
+    let fixed = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    env.ledger().with_mut(|li| li.timestamp = 4 * 7 * 86_400);
+
+    let item = |contract_id: u32, amount: PayrollAmount| PayrollItem { contract_id, amount };
+    let items = Vec::from_array(&env, [
+        item(short, PayrollAmount::Amount(300)),
+        item(keeper, PayrollAmount::DuePeriods),
+        item(fixed, PayrollAmount::Amount(300)),
+    ]);
+    let report = lifecycle_client.run_payroll(&clerk, &items, &PayrollMode::BestEffort);
+    assert_eq!(report.outcomes.get(0).unwrap().skipped, Some(LifecycleError::VaultPaymentFailed as u32));
+    assert_eq!(report.outcomes.get(1).unwrap().paid, 400);
+    // Had the skipped item counted towards the cap, this one would not fit.
+    assert_eq!(report.outcomes.get(2).unwrap().paid, 300);
+
+    // The skipped item left nothing behind.
+    assert_eq!(lifecycle_client.get_contract(&short).paid_amount, 0);
+    assert_eq!(lifecycle_client.get_payment_count(&short), 0);
+    assert_eq!(lifecycle_client.get_operator_usage(&employer, &clerk), 700);
+    assert_eq!(token_client.balance(&employee), 700);
+
+    // The completed contract's keeper pool went back once the run paid out.
+    assert_eq!(lifecycle_client.get_contract(&keeper).status, ContractStatus::Completed);
+    assert_eq!(lifecycle_client.get_keeper_terms(&keeper), None);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 100 + 700);
+}
+
+#[test]
+fn test_payroll_run_executes_only_after_approval() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let (clerk, controller) = (Address::generate(&env), Address::generate(&env));
+    let identity_id = env.register(IdentityManagementContract, ());
+    let identity_client = IdentityManagementContractClient::new(&env, &identity_id);
+    identity_client.initialize(&admin);
+    lifecycle_client.set_identity_contract(&lifecycle_client.get_admin(), &identity_id);
+    let scopes = Vec::from_array(&env, [OperatorScope::ProcessPayments]);
+    identity_client.grant_operator(&employer, &clerk, &scopes, &None, &None);
+    identity_client.grant_operator(&employer, &controller, &scopes, &None, &None);
+
+    let fixed = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let payg = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1000, &token_address,
+    );
+    let item = |contract_id: u32, amount: i128| PayrollItem { contract_id, amount: PayrollAmount::Amount(amount) };
+    let items = Vec::from_array(&env, [item(fixed, 300), item(payg, 200)]);
+
+    // Runs need approvers, and the employer picks them, not the preparer.
+    assert_eq!(
+        lifecycle_client.try_draft_payroll_run(&clerk, &employer, &items),
+        Err(Ok(LifecycleError::InvalidPayrollRun))
+    );
+    lifecycle_client.set_payroll_approvers(&employer, &Vec::from_array(&env, [clerk.clone(), controller.clone()]), &1);
+    // A preparer who is also an approver could sign off on their own run.
+    assert_eq!(
+        lifecycle_client.try_draft_payroll_run(&clerk, &employer, &items),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    let approvers = Vec::from_array(&env, [employer.clone(), controller.clone()]);
+    lifecycle_client.set_payroll_approvers(&employer, &approvers, &2);
+    assert_eq!(
+        lifecycle_client.get_payroll_approvers(&employer),
+        Some(PayrollApprovers { approvers: approvers.clone(), threshold: 2 })
+    );
+    let run_id = lifecycle_client.draft_payroll_run(&clerk, &employer, &items);
+    let run = lifecycle_client.get_payroll_run(&run_id).unwrap();
+    assert_eq!(run.status, PayrollRunStatus::Draft);
+    assert_eq!((run.approvers, run.threshold), (approvers, 2));
+    assert_eq!(run.lines.get(0).unwrap().payee, employee);
+    assert_eq!(run.totals.len(), 1);
+    assert_eq!(run.totals.get(0).unwrap().amount, 500);
+
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &run_id).map(|_| ()),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+    assert_eq!(
+        lifecycle_client.try_approve_payroll_run(&clerk, &run_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    lifecycle_client.approve_payroll_run(&employer, &run_id);
+    assert_eq!(
+        lifecycle_client.try_approve_payroll_run(&employer, &run_id),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+    lifecycle_client.approve_payroll_run(&controller, &run_id);
+    assert_eq!(lifecycle_client.get_payroll_run(&run_id).unwrap().status, PayrollRunStatus::Approved);
+
+    // Editing an approved run sends it back for a fresh round of approvals.
+    lifecycle_client.update_payroll_run(&clerk, &run_id, &Vec::from_array(&env, [item(fixed, 400), item(payg, 200)]));
+    let run = lifecycle_client.get_payroll_run(&run_id).unwrap();
+    assert_eq!(run.status, PayrollRunStatus::Draft);
+    assert!(run.approvals.is_empty());
+    assert_eq!(run.totals.get(0).unwrap().amount, 600);
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &run_id).map(|_| ()),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+
+    lifecycle_client.approve_payroll_run(&controller, &run_id);
+    lifecycle_client.approve_payroll_run(&employer, &run_id);
+    let report = lifecycle_client.execute_payroll_run(&clerk, &run_id);
+    assert_eq!(report.run_id, run_id);
+    assert_eq!(report.outcomes.get(0).unwrap().paid, 400);
+    assert_eq!(token_client.balance(&employee), 600);
+    assert_eq!(lifecycle_client.get_contract(&fixed).paid_amount, 400);
+    assert_eq!(lifecycle_client.get_payroll_run(&run_id).unwrap().status, PayrollRunStatus::Executed);
+
+    // An executed run can be neither run again nor edited.
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &run_id).map(|_| ()),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+    assert_eq!(
+        lifecycle_client.try_update_payroll_run(&clerk, &run_id, &items),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+}
+
+#[test]
+fn test_payroll_run_moves_nothing_for_outsiders_or_stale_runs() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let (clerk, controller, stranger) = (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+    let identity_id = env.register(IdentityManagementContract, ());
+    let identity_client = IdentityManagementContractClient::new(&env, &identity_id);
+    identity_client.initialize(&admin);
+    lifecycle_client.set_identity_contract(&lifecycle_client.get_admin(), &identity_id);
+    let scopes = Vec::from_array(&env, [OperatorScope::ProcessPayments]);
+    identity_client.grant_operator(&employer, &clerk, &scopes, &None, &None);
+    identity_client.grant_operator(&employer, &controller, &scopes, &None, &None);
+
+    let fixed = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let payg = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1000, &token_address,
+    );
+    let foreign = create_active_contract(
+        &lifecycle_client, &stranger, &employee, ContractType::FixedRate, 1000, &token_address,
+    );
+    let item = |contract_id: u32, amount: i128| PayrollItem { contract_id, amount: PayrollAmount::Amount(amount) };
+    let items = Vec::from_array(&env, [item(fixed, 300), item(payg, 200)]);
+    let approvers = Vec::from_array(&env, [employer.clone(), controller.clone()]);
+
+    let twice = Vec::from_array(&env, [controller.clone(), controller.clone()]);
+    assert_eq!(
+        lifecycle_client.try_set_payroll_approvers(&employer, &twice, &2),
+        Err(Ok(LifecycleError::InvalidPayrollRun))
+    );
+    assert_eq!(
+        lifecycle_client.try_set_payroll_approvers(&employer, &approvers, &3),
+        Err(Ok(LifecycleError::InvalidPayrollRun))
+    );
+    assert_eq!(
+        lifecycle_client.try_set_payroll_approvers(&employer, &approvers, &0),
+        Err(Ok(LifecycleError::InvalidPayrollRun))
+    );
+    lifecycle_client.set_payroll_approvers(&employer, &approvers, &1);
+
+    assert_eq!(
+        lifecycle_client.try_draft_payroll_run(&stranger, &employer, &items),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_draft_payroll_run(&clerk, &employer, &Vec::from_array(&env, [item(foreign, 300)])),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    // The employer is an approver here, so they cannot prepare runs themselves.
+    assert_eq!(
+        lifecycle_client.try_draft_payroll_run(&employer, &employer, &items),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // Outsiders can neither edit, approve nor cancel a run.
+    let run_id = lifecycle_client.draft_payroll_run(&clerk, &employer, &items);
+    // Changing the approvers later does not change who signs off on it.
+    lifecycle_client.set_payroll_approvers(&employer, &Vec::from_array(&env, [stranger.clone()]), &1);
+    assert_eq!(
+        lifecycle_client.try_update_payroll_run(&controller, &run_id, &items),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_approve_payroll_run(&stranger, &run_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_cancel_payroll_run(&controller, &run_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    // A listed approver whose delegation was revoked no longer counts.
+    identity_client.revoke_operator(&employer, &controller);
+    assert_eq!(
+        lifecycle_client.try_approve_payroll_run(&controller, &run_id),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+
+    // A contract that closed after approval fails the whole run: the other
+    // contract on it is not paid either.
+    lifecycle_client.approve_payroll_run(&employer, &run_id);
+    lifecycle_client.cancel_contract(&employer, &fixed);
+    assert_eq!(unspent_reservation(&vault_client, fixed), 0);
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &run_id).map(|_| ()),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(lifecycle_client.get_payroll_run(&run_id).unwrap().status, PayrollRunStatus::Approved);
+    assert_eq!(token_client.balance(&employee), 0);
+    assert_eq!(lifecycle_client.get_contract(&payg).paid_amount, 0);
+    assert_eq!(lifecycle_client.get_payment_count(&payg), 0);
+    assert_eq!(unspent_reservation(&vault_client, payg), 1000);
+
+    // A cancelled run is closed for good.
+    lifecycle_client.cancel_payroll_run(&clerk, &run_id);
+    assert_eq!(lifecycle_client.get_payroll_run(&run_id).unwrap().status, PayrollRunStatus::Cancelled);
+    assert_eq!(
+        lifecycle_client.try_approve_payroll_run(&employer, &run_id),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &run_id).map(|_| ()),
+        Err(Ok(LifecycleError::InvalidPayrollRunState))
+    );
+    assert_eq!(
+        lifecycle_client.try_execute_payroll_run(&clerk, &99).map(|_| ()),
+        Err(Ok(LifecycleError::PayrollRunNotFound))
+    );
+}
+
+#[test]
+fn test_keeper_runs_due_scheduled_payments() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let keeper = Address::generate(&env);
+
+    let metadata = create_metadata(&employer, &employee, ContractType::FixedRate, 400, &token_address);
+    let contract_id = lifecycle_client.create_contract(
+        &employer, &employee, &ContractType::FixedRate, &400, &token_address, &metadata, &None,
+        &Some(weekly_schedule(100, 1_000, 4)), &OFFER_EXPIRY,
+    );
+    assert_eq!(
+        lifecycle_client.try_execute_scheduled_payment(&keeper, &contract_id),
+        Err(Ok(LifecycleError::KeeperPaymentsDisabled))
+    );
+
+    // The employer funds a reward for each of the four periods up front.
+    lifecycle_client.set_keeper_reward(&employer, &contract_id, &5);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 420);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 15 * 86_400);
+    assert_eq!(lifecycle_client.execute_scheduled_payment(&keeper, &contract_id), 2);
+    // Only the keeper signed; the employer authorized the run in advance.
+    let auths = env.auths();
+    assert!(!auths.is_empty() && auths.iter().all(|(address, _)| *address == keeper));
+    assert_eq!(token_client.balance(&employee), 200);
+    assert_eq!(token_client.balance(&keeper), 5);
+
+    assert_eq!(
+        lifecycle_client.try_execute_scheduled_payment(&keeper, &contract_id),
+        Err(Ok(LifecycleError::NoPaymentDue))
+    );
+    assert_eq!(token_client.balance(&keeper), 5);
+
+    // Completing the schedule hands the unused rewards back to the employer.
+    env.ledger().with_mut(|li| li.timestamp = 1_000 + 28 * 86_400);
+    assert_eq!(lifecycle_client.execute_scheduled_payment(&keeper, &contract_id), 2);
+    assert_eq!(token_client.balance(&employee), 400);
+    assert_eq!(token_client.balance(&keeper), 10);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Completed);
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id), None);
+
+    let reservation = vault_client.get_reservation(&contract_id).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (410, 410));
+}
+
+// Creates a four-week scheduled FixedRate offer of 400 whose keepers earn 5 a
+// period, so 20 is held for them on top of the contract's own funds.
+fn create_keeper_offer(
+    env: &Env,
+    lifecycle_client: &LifecycleContractClient,
+    employer: &Address,
+    employee: &Address,
+    token_address: &Address,
+) -> u32 {
+    let metadata = create_metadata(employer, employee, ContractType::FixedRate, 400, token_address);
+    let contract_id = lifecycle_client.create_contract(
+        employer, employee, &ContractType::FixedRate, &400, token_address, &metadata, &None,
+        &Some(weekly_schedule(100, env.ledger().timestamp(), 4)), &OFFER_EXPIRY,
+    );
+    lifecycle_client.set_keeper_reward(employer, &contract_id, &5);
+    contract_id
+}
+
+#[test]
+fn test_keeper_rewards_paid_only_while_contract_runs() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let keeper = Address::generate(&env);
+
+    let unscheduled = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::FixedRate, 400, &token_address,
+    );
+    assert_eq!(
+        lifecycle_client.try_set_keeper_reward(&employer, &unscheduled, &5),
+        Err(Ok(LifecycleError::InvalidSchedule))
+    );
+
+    let contract_id = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    assert_eq!(
+        lifecycle_client.try_set_keeper_reward(&employee, &contract_id, &50),
+        Err(Ok(LifecycleError::NotAuthorized))
+    );
+    assert_eq!(
+        lifecycle_client.try_set_keeper_reward(&employer, &contract_id, &-1),
+        Err(Ok(LifecycleError::InvalidAmount))
+    );
+
+    // Nothing runs, and no reward is paid, before the offer is accepted.
+    assert_eq!(
+        lifecycle_client.try_execute_scheduled_payment(&keeper, &contract_id),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    env.ledger().with_mut(|li| li.timestamp = 8 * 86_400);
+    lifecycle_client.execute_scheduled_payment(&keeper, &contract_id);
+    assert_eq!(token_client.balance(&keeper), 5);
+    assert_eq!(token_client.balance(&employee), 100);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).paid_amount, 100);
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id).unwrap().reserved, 15);
+
+    // Suspension pauses keepers along with everything else.
+    lifecycle_client.suspend_contract(&employer, &contract_id, &String::from_str(&env, "audit"));
+    env.ledger().with_mut(|li| li.timestamp = 15 * 86_400);
+    assert_eq!(
+        lifecycle_client.try_execute_scheduled_payment(&keeper, &contract_id),
+        Err(Ok(LifecycleError::ContractNotActive))
+    );
+    assert_eq!(token_client.balance(&employee), 100);
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id).unwrap().reserved, 15);
+
+    // Resigning ends keeper authorization and releases the rest of the pool.
+    lifecycle_client.resign(&employee, &contract_id);
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Cancelled);
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id), None);
+    assert_eq!(
+        lifecycle_client.try_execute_scheduled_payment(&keeper, &contract_id),
+        Err(Ok(LifecycleError::KeeperPaymentsDisabled))
+    );
+    assert_eq!(token_client.balance(&keeper), 5);
+    assert_eq!(unspent_reservation(&vault_client, contract_id), 0);
+    // Only the unscheduled contract is still reserved.
+    assert_eq!(vault_client.get_total_reserved(&token_address), 400);
+    // The day worked before the suspension is settled; the week on hold is not.
+    assert_eq!(token_client.balance(&employee), 114);
+    assert_eq!(vault_client.get_treasury_stats(&token_address).total_liquidity, 10_000 - 400 - 114 - 5);
+}
+
+#[test]
+fn test_keeper_pool_released_when_offer_is_withdrawn() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let rejected = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    let cancelled = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 840);
+
+    lifecycle_client.reject_contract(&employee, &rejected);
+    lifecycle_client.cancel_contract(&employer, &cancelled);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(lifecycle_client.get_keeper_terms(&rejected), None);
+    assert_eq!(lifecycle_client.get_keeper_terms(&cancelled), None);
+}
+
+#[test]
+fn test_keeper_pool_released_when_dispute_completes_contract() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let contract_id = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+    lifecycle_client.raise_dispute(&employee, &contract_id, &None, &String::from_str(&env, "unpaid"));
+    lifecycle_client.resolve_dispute(&lifecycle_client.get_admin(), &contract_id, &100);
+
+    assert_eq!(lifecycle_client.get_contract(&contract_id).status, ContractStatus::Completed);
+    assert_eq!(token_client.balance(&employee), 100);
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id), None);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+}
+
+#[test]
+fn test_keeper_pool_released_before_asset_switch() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let other_asset = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    vault_client.whitelist_asset(&vault_client.get_admin(), &other_asset, &true);
+    fund_vault(&env, &vault_id, &other_asset, 10_000);
+
+    let contract_id = create_keeper_offer(&env, &lifecycle_client, &employer, &employee, &token_address);
+    lifecycle_client.accept_contract(&employee, &contract_id);
+
+    let mut changes = no_changes();
+    changes.asset = Some(other_asset.clone());
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employee, &contract_id);
+
+    assert_eq!(lifecycle_client.get_keeper_terms(&contract_id), None);
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(vault_client.get_reservation(&contract_id).unwrap().reserved, 400);
+    assert_eq!(vault_client.get_total_reserved(&other_asset), 400);
+}
+
+#[test]
+fn test_dispute_excludes_outstanding_advance() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, token_client) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    env.mock_all_auths_allowing_non_root_auth();
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+    let arbiter = lifecycle_client.get_admin();
+    let policy = AdvancePolicy { max_share_bps: 5_000, auto_approve_limit: 200 };
+
+    for employee_amount in [0, 100] {
+        let contract_id = create_active_contract(
+            &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1_000, &token_address,
+        );
+        lifecycle_client.set_advance_policy(&employer, &contract_id, &policy);
+        let advance_id = lifecycle_client.request_advance(&employee, &contract_id, &200);
+
+        // The advance has already been paid, so only 800 is left to argue over.
+        lifecycle_client.raise_dispute(&employee, &contract_id, &None, &String::from_str(&env, "terms"));
+        assert_eq!(lifecycle_client.get_dispute(&contract_id).unwrap().amount, 800);
+
+        let before = token_client.balance(&employee);
+        lifecycle_client.resolve_dispute(&arbiter, &contract_id, &employee_amount);
+        assert_eq!(token_client.balance(&employee), before + employee_amount);
+
+        let contract = lifecycle_client.get_contract(&contract_id);
+        assert_eq!(contract.status, ContractStatus::Completed);
+        assert_eq!(contract.paid_amount, 200 + employee_amount);
+        assert_eq!(lifecycle_client.get_advance(&contract_id, &advance_id).unwrap().status, AdvanceStatus::Repaid);
+        assert_eq!(lifecycle_client.get_outstanding_advance(&contract_id), 0);
+
+        let reservation = vault_client.get_reservation(&contract_id).unwrap();
+        assert_eq!(reservation.reserved, reservation.spent);
+    }
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+}
+
+#[test]
+fn test_amendment_keeps_outstanding_advance_reserved() {
+    let (env, admin, employer, employee) = setup_env();
+    let (lifecycle_client, vault_id, _, token_address, _) =
+        setup_contracts(&env, &admin, &employer, &employee);
+    fund_vault(&env, &vault_id, &token_address, 10_000);
+    let vault_client = VaultContractClient::new(&env, &vault_id);
+
+    let contract_id = create_active_contract(
+        &lifecycle_client, &employer, &employee, ContractType::PayAsYouGo, 1_000, &token_address,
+    );
+    lifecycle_client.set_advance_policy(
+        &employer, &contract_id, &AdvancePolicy { max_share_bps: 5_000, auto_approve_limit: 200 },
+    );
+    lifecycle_client.request_advance(&employee, &contract_id, &200);
+
+    // The total cannot drop below what the employee already holds.
+    let mut changes = no_changes();
+    changes.total_amount = Some(150);
+    assert_eq!(
+        lifecycle_client.try_propose_amendment(&employer, &contract_id, &changes),
+        Err(Ok(LifecycleError::InvalidAmendment))
+    );
+
+    let other_asset = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    vault_client.whitelist_asset(&vault_client.get_admin(), &other_asset, &true);
+    fund_vault(&env, &vault_id, &other_asset, 10_000);
+
+    changes.total_amount = None;
+    changes.asset = Some(other_asset.clone());
+    lifecycle_client.propose_amendment(&employer, &contract_id, &changes);
+    lifecycle_client.approve_amendment(&employee, &contract_id);
+
+    assert_eq!(vault_client.get_total_reserved(&token_address), 0);
+    assert_eq!(vault_client.get_total_reserved(&other_asset), 800);
 }
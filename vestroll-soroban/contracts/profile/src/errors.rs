@@ -14,4 +14,6 @@ pub enum ContractError {
     MissingTrustline = 8,
     NotAWorker = 9,
     NotAnOrganization = 10,
+    AlreadyMigrated = 11,
+    MigrationPending = 12,
 }
\ No newline at end of file
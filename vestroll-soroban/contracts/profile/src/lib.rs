@@ -6,12 +6,20 @@ mod types;
 #[cfg(test)]
 mod test;
 
-use soroban_sdk::{contract, contractimpl, Address, Env, String, Vec, Map, symbol_short, Symbol};
+use soroban_sdk::{contract, contractimpl, Address, BytesN, Env, IntoVal, String, Val, Vec, Map, symbol_short, Symbol};
+use vestroll_common::{
+    complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm, INSTANCE_BUMP_AMOUNT,
+    INSTANCE_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT, RECORD_BUMP_THRESHOLD,
+};
 
 use crate::errors::ContractError;
 use crate::storage::{DataKey, WorkerWallet};
 use crate::types::{Profile, ProfileType};
 
+/// Storage schema version this code expects. Version 1 keeps profiles in
+/// per-record persistent storage.
+const SCHEMA_VERSION: u32 = 1;
+
 // ========================================================================
 // Contract
 // ========================================================================
@@ -34,6 +42,7 @@ impl ProfileContract {
 
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Initialized, &true);
+        set_schema_version(&env, SCHEMA_VERSION);
 
         Ok(())
     }
@@ -70,20 +79,20 @@ impl ProfileContract {
             is_active: true,
         };
 
-        env.storage().instance().set(&DataKey::Profile(user), &profile);
+        Self::persist(&env, &DataKey::Profile(user), &profile);
 
         Ok(profile)
     }
 
     pub fn get_profile(env: Env, user: Address) -> Result<Profile, ContractError> {
         env.storage()
-            .instance()
+            .persistent()
             .get(&DataKey::Profile(user))
             .ok_or(ContractError::ProfileNotFound)
     }
 
     pub fn has_profile(env: Env, user: Address) -> bool {
-        env.storage().instance().has(&DataKey::Profile(user))
+        env.storage().persistent().has(&DataKey::Profile(user))
     }
 
     pub fn deactivate_profile(env: Env, user: Address) -> Result<(), ContractError> {
@@ -96,7 +105,7 @@ impl ProfileContract {
         let mut profile = Self::get_profile(env.clone(), user.clone())?;
         profile.is_active = false;
 
-        env.storage().instance().set(&DataKey::Profile(user), &profile);
+        Self::persist(&env, &DataKey::Profile(user), &profile);
 
         Ok(())
     }
@@ -137,7 +146,7 @@ impl ProfileContract {
             is_active: true,
         };
 
-        env.storage().instance().set(&DataKey::WorkerWallet(worker.clone()), &wallet);
+        Self::persist(&env, &DataKey::WorkerWallet(worker.clone()), &wallet);
 
         // Trigger initial trustline verification
         Self::verify_trustline(env, worker)?;
@@ -147,13 +156,13 @@ impl ProfileContract {
 
     pub fn get_worker_wallet(env: Env, worker: Address) -> Result<WorkerWallet, ContractError> {
         env.storage()
-            .instance()
+            .persistent()
             .get(&DataKey::WorkerWallet(worker))
             .ok_or(ContractError::ProfileNotFound)
     }
 
     pub fn has_wallet_registered(env: Env, worker: Address) -> bool {
-        env.storage().instance().has(&DataKey::WorkerWallet(worker))
+        env.storage().persistent().has(&DataKey::WorkerWallet(worker))
     }
 
     pub fn update_wallet_address(
@@ -177,7 +186,7 @@ impl ProfileContract {
         wallet.trustline_verified = false;
         wallet.last_verified = env.ledger().timestamp();
 
-        env.storage().instance().set(&DataKey::WorkerWallet(worker), &wallet);
+        Self::persist(&env, &DataKey::WorkerWallet(worker), &wallet);
 
         Ok(wallet)
     }
@@ -200,7 +209,7 @@ impl ProfileContract {
         updated_wallet.trustline_verified = trustline_exists;
         updated_wallet.last_verified = env.ledger().timestamp();
 
-        env.storage().instance().set(&DataKey::WorkerWallet(worker), &updated_wallet);
+        Self::persist(&env, &DataKey::WorkerWallet(worker), &updated_wallet);
 
         Ok(trustline_exists)
     }
@@ -270,7 +279,7 @@ impl ProfileContract {
 
         let key = DataKey::OrgWorkers(organization.clone());
         let mut workers: Vec<Address> = env.storage()
-            .instance()
+            .persistent()
             .get(&key)
             .unwrap_or(Vec::new(&env));
 
@@ -285,12 +294,12 @@ impl ProfileContract {
 
         if !exists {
             workers.push_back(worker.clone());
-            env.storage().instance().set(&key, &workers);
+            Self::persist(&env, &key, &workers);
 
             // Update worker count
             let count_key = DataKey::WorkerCount(organization);
-            let count: u32 = env.storage().instance().get(&count_key).unwrap_or(0);
-            env.storage().instance().set(&count_key, &(count + 1));
+            let count: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+            Self::persist(&env, &count_key, &(count + 1));
         }
 
         Ok(())
@@ -302,7 +311,7 @@ impl ProfileContract {
         }
 
         env.storage()
-            .instance()
+            .persistent()
             .get(&DataKey::OrgWorkers(organization))
             .unwrap_or(Vec::new(&env))
     }
@@ -313,11 +322,125 @@ impl ProfileContract {
         }
 
         env.storage()
-            .instance()
+            .persistent()
             .get(&DataKey::WorkerCount(organization))
             .unwrap_or(0)
     }
 
+    // ====================================================================
+    // Storage
+    // ====================================================================
+
+    /// Extends the TTL of everything stored for `user`: profile, worker
+    /// wallet and, for organizations, the worker list. Anyone may call this.
+    pub fn bump_profile(env: Env, user: Address) {
+        env.storage().instance().extend_ttl(INSTANCE_BUMP_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        for key in Self::user_keys(&env, &user).iter() {
+            if env.storage().persistent().has(&key) {
+                env.storage().persistent().extend_ttl(&key, RECORD_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT);
+            }
+        }
+    }
+
+    /// Records how many users still have records in instance storage, as
+    /// counted off-chain from the contract's instance entry. `migrate` waits
+    /// until `migrate_storage` has moved every one of them.
+    pub fn begin_storage_migration(env: Env, admin: Address, legacy_users: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        if admin != Self::get_admin(env.clone())? {
+            return Err(ContractError::Unauthorized);
+        }
+        if schema_version(&env) >= SCHEMA_VERSION {
+            return Err(ContractError::AlreadyMigrated);
+        }
+
+        env.storage().instance().set(&DataKey::LegacyUsers, &legacy_users);
+        Ok(())
+    }
+
+    /// One-off migration for deployments that predate per-record storage:
+    /// moves the given users' records out of instance storage. Safe to run
+    /// repeatedly and in batches. Returns how many users had data moved.
+    pub fn migrate_storage(env: Env, admin: Address, users: Vec<Address>) -> Result<u32, ContractError> {
+        admin.require_auth();
+        if admin != Self::get_admin(env.clone())? {
+            return Err(ContractError::Unauthorized);
+        }
+
+        let mut migrated = 0;
+        for user in users.iter() {
+            let mut moved = false;
+            for key in [DataKey::Profile(user.clone()), DataKey::WorkerWallet(user.clone())] {
+                let value: Option<Val> = env.storage().instance().get(&key);
+                if let Some(value) = value {
+                    Self::persist(&env, &key, &value);
+                    env.storage().instance().remove(&key);
+                    moved = true;
+                }
+            }
+
+            // Worker lists may already have grown in persistent storage since the upgrade.
+            let key = DataKey::OrgWorkers(user.clone());
+            let legacy: Option<Vec<Address>> = env.storage().instance().get(&key);
+            if let Some(legacy) = legacy {
+                let mut workers: Vec<Address> = env.storage().persistent().get(&key).unwrap_or(Vec::new(&env));
+                for worker in legacy.iter() {
+                    if !workers.contains(&worker) {
+                        workers.push_back(worker);
+                    }
+                }
+                Self::persist(&env, &key, &workers);
+                Self::persist(&env, &DataKey::WorkerCount(user.clone()), &workers.len());
+                env.storage().instance().remove(&key);
+                env.storage().instance().remove(&DataKey::WorkerCount(user));
+                moved = true;
+            }
+
+            if moved {
+                migrated += 1;
+            }
+        }
+
+        let pending: Option<u32> = env.storage().instance().get(&DataKey::LegacyUsers);
+        if let Some(pending) = pending {
+            env.storage().instance().set(&DataKey::LegacyUsers, &pending.saturating_sub(migrated));
+        }
+
+        Ok(migrated)
+    }
+
+    // ====================================================================
+    // Upgrades
+    // ====================================================================
+
+    /// Replaces the profile contract's code, keeping its storage. Run
+    /// `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        upgrade_wasm(&env, &Self::get_admin(env.clone())?, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Stamps storage with schema version 1 once `migrate_storage` has moved
+    /// every user announced through `begin_storage_migration`; fails with
+    /// `MigrationPending` until then.
+    pub fn migrate(env: Env) -> Result<u32, ContractError> {
+        let from = pending_migration(&env, &Self::get_admin(env.clone())?, SCHEMA_VERSION)
+            .ok_or(ContractError::AlreadyMigrated)?;
+
+        let pending: Option<u32> = env.storage().instance().get(&DataKey::LegacyUsers);
+        if from == 0 && pending != Some(0) {
+            return Err(ContractError::MigrationPending);
+        }
+
+        env.storage().instance().remove(&DataKey::LegacyUsers);
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
+
     // ====================================================================
     // Getters
     // ====================================================================
@@ -367,4 +490,25 @@ impl ProfileContract {
     
         stats
     }
+
+    // ====================================================================
+    // Internal Helpers
+    // ====================================================================
+
+    /// Writes a per-user persistent record and extends its TTL along with
+    /// the contract instance.
+    fn persist<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, RECORD_BUMP_THRESHOLD, RECORD_BUMP_AMOUNT);
+        env.storage().instance().extend_ttl(INSTANCE_BUMP_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    fn user_keys(env: &Env, user: &Address) -> Vec<DataKey> {
+        Vec::from_array(env, [
+            DataKey::Profile(user.clone()),
+            DataKey::WorkerWallet(user.clone()),
+            DataKey::OrgWorkers(user.clone()),
+            DataKey::WorkerCount(user.clone()),
+        ])
+    }
 }
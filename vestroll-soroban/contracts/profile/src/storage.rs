@@ -9,6 +9,7 @@ pub enum DataKey {
     WorkerWallet(Address),
     OrgWorkers(Address),
     WorkerCount(Address),
+    LegacyUsers,
 }
 
 #[contracttype]
@@ -1,9 +1,12 @@
 #![cfg(test)]
 
 use super::{ProfileContract, ProfileContractClient};
-use crate::types::ProfileType;
-use soroban_sdk::testutils::{Address as _, Ledger as _};
-use soroban_sdk::{Address, Env, String, symbol_short};
+use crate::errors::ContractError;
+use crate::storage::DataKey;
+use crate::types::{Profile, ProfileType};
+use soroban_sdk::testutils::{storage::Persistent as _, Address as _, Ledger as _};
+use soroban_sdk::{Address, Env, String, Vec, symbol_short};
+use vestroll_common::{UpgradeKey, RECORD_BUMP_AMOUNT};
 
 fn setup_test() -> (Env, ProfileContractClient<'static>, Address) {
     let env = Env::default();
@@ -145,3 +148,78 @@ fn test_operations_without_initialization() {
     client.create_profile(&user, &String::from_str(&env, "Fail"), &false);
 }
 
+
+#[test]
+fn test_profile_records_are_persistent_and_bumpable() {
+    let (env, client, _admin) = setup_test();
+    let worker = create_test_worker(&env, &client, "Worker");
+    let key = DataKey::Profile(worker.clone());
+
+    env.as_contract(&client.address, || {
+        assert!(!env.storage().instance().has(&key));
+        assert_eq!(env.storage().persistent().get_ttl(&key), RECORD_BUMP_AMOUNT);
+    });
+
+    // Keep the instance alive so only the records run low on TTL.
+    env.as_contract(&client.address, || env.storage().instance().extend_ttl(200 * 17_280, 200 * 17_280));
+    env.ledger().with_mut(|li| li.sequence_number += 100 * 17_280);
+    client.bump_profile(&worker);
+    env.as_contract(&client.address, || {
+        assert_eq!(env.storage().persistent().get_ttl(&key), RECORD_BUMP_AMOUNT);
+    });
+}
+
+#[test]
+fn test_migrate_legacy_instance_records() {
+    let (env, client, admin) = setup_test();
+    let org = create_test_org(&env, &client, "Org");
+    let legacy_worker = Address::generate(&env);
+    let new_worker = create_test_worker(&env, &client, "New");
+
+    // Seed records the way older deployments stored them.
+    env.as_contract(&client.address, || {
+        let profile = Profile {
+            id: legacy_worker.clone(),
+            name: String::from_str(&env, "Legacy"),
+            profile_type: ProfileType::Worker,
+            created_at: 1,
+            is_active: true,
+        };
+        env.storage().instance().set(&DataKey::Profile(legacy_worker.clone()), &profile);
+        env.storage().instance().set(&DataKey::OrgWorkers(org.clone()), &Vec::from_array(&env, [legacy_worker.clone()]));
+        env.storage().instance().set(&DataKey::WorkerCount(org.clone()), &1u32);
+    });
+    assert!(!client.has_profile(&legacy_worker));
+    client.add_worker_to_organization(&org, &new_worker);
+
+    // The schema is only stamped once every legacy user has been moved.
+    env.as_contract(&client.address, || env.storage().instance().remove(&UpgradeKey::SchemaVersion));
+    assert_eq!(client.try_migrate(), Err(Ok(ContractError::MigrationPending)));
+    client.begin_storage_migration(&admin, &2);
+    assert_eq!(client.migrate_storage(&admin, &Vec::from_array(&env, [org.clone()])), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(ContractError::MigrationPending)));
+
+    let users = Vec::from_array(&env, [legacy_worker.clone(), org.clone(), Address::generate(&env)]);
+    assert_eq!(client.migrate_storage(&admin, &users), 1);
+    assert_eq!(client.migrate(), 1);
+    assert_eq!(client.version(), 1);
+
+    assert_eq!(client.get_profile(&legacy_worker).name, String::from_str(&env, "Legacy"));
+    assert_eq!(client.get_organization_workers(&org).len(), 2);
+    assert_eq!(client.get_worker_count(&org), 2);
+    assert_eq!(client.migrate_storage(&admin, &users), 0);
+    assert_eq!(client.try_begin_storage_migration(&admin, &1), Err(Ok(ContractError::AlreadyMigrated)));
+}
+
+#[test]
+fn test_migrate_runs_once_per_version() {
+    let (env, client, admin) = setup_test();
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(ContractError::AlreadyMigrated)));
+
+    env.as_contract(&client.address, || env.storage().instance().remove(&UpgradeKey::SchemaVersion));
+    assert_eq!(client.version(), 0);
+    client.begin_storage_migration(&admin, &0);
+    assert_eq!(client.migrate(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(ContractError::AlreadyMigrated)));
+}
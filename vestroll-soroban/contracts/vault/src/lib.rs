@@ -1,11 +1,17 @@
 #![no_std]
 mod test_vault;
 
-use soroban_sdk::{contract, contractimpl, token, Address, Env, Vec};
+use soroban_sdk::{contract, contractimpl, token, Address, BytesN, Env, Vec};
 use vestroll_common::{
-    DataKey, Payment, PayoutEntry, TreasuryStats, VaultError, BATCH_DONE, PAUSED, PAYOUT, UNPAUSED, INVOICE,
+    complete_migration, pending_migration, schema_version, set_schema_version, upgrade_wasm, DataKey, FeeConfig, Payment, PayoutEntry,
+    Reservation, ReservedPayout, TreasuryStats, VaultError, BATCH_DONE, FEE, FEES_OUT, PAUSED, PAYOUT, UNPAUSED, INVOICE, RELEASED,
+    RESERVED,
 };
 
+/// Storage schema version this code expects.
+const SCHEMA_VERSION: u32 = 1;
+const BASIS_POINTS: u32 = 10_000;
+
 #[contract]
 pub struct VaultContract;
 
@@ -24,6 +30,7 @@ impl VaultContract {
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Token, &token);
         env.storage().instance().set(&DataKey::Paused, &false);
+        set_schema_version(&env, SCHEMA_VERSION);
         
         // Auto-whitelist the primary token
         Self::internal_whitelist_asset(&env, token, true);
@@ -103,6 +110,7 @@ impl VaultContract {
                 return Err(VaultError::InsufficientLockedFunds);
             }
 
+            Self::charge_fee(&env, &asset, amount)?;
             Self::internal_transfer(&env, &asset, &recipient, amount)?;
 
             // Update Storage
@@ -144,11 +152,9 @@ impl VaultContract {
             return Err(VaultError::InvalidAmount);
         }
 
-        let client = token::Client::new(&env, &asset);
-        let balance = client.balance(&env.current_contract_address());
-        let locked: i128 = env.storage().persistent().get(&DataKey::TotalLocked(asset.clone())).unwrap_or(0);
-
-        let available = balance - locked;
+        // The fee is set aside first so the invoice cannot spend it.
+        Self::charge_fee(&env, &asset, amount)?;
+        let available = Self::available_balance(&env, &asset);
 
         if amount > available {
             return Err(VaultError::InsufficientBalance);
@@ -218,11 +224,7 @@ impl VaultContract {
             return Err(VaultError::InvalidAmount);
         }
 
-        let client = token::Client::new(&env, &asset);
-        let balance = client.balance(&env.current_contract_address());
-        let locked: i128 = env.storage().persistent().get(&DataKey::TotalLocked(asset.clone())).unwrap_or(0);
-
-        let available = balance - locked;
+        let available = Self::available_balance(&env, &asset);
 
         if amount > available {
             return Err(VaultError::InsufficientBalance);
@@ -231,6 +233,138 @@ impl VaultContract {
         Self::internal_transfer(&env, &asset, &to, amount)
     }
 
+    // ====================================================================
+    // Contract Reservations
+    // ====================================================================
+
+    /// Registers the lifecycle contract allowed to reserve and spend funds.
+    pub fn set_lifecycle_contract(env: Env, admin: Address, lifecycle: Address) -> Result<(), VaultError> {
+        Self::check_admin(&env, &admin)?;
+        env.storage().instance().set(&DataKey::Lifecycle, &lifecycle);
+        Ok(())
+    }
+
+    /// Sets `amount` of unallocated liquidity aside for a lifecycle contract.
+    /// Calling it again tops the reservation up.
+    pub fn reserve(env: Env, contract_id: u32, asset: Address, amount: i128) -> Result<(), VaultError> {
+        Self::check_lifecycle(&env)?;
+
+        if Self::is_paused(&env) {
+            return Err(VaultError::ContractPaused);
+        }
+
+        if amount <= 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+
+        if !Self::is_whitelisted(&env, &asset) {
+            return Err(VaultError::AssetNotWhitelisted);
+        }
+
+        if amount > Self::available_balance(&env, &asset) {
+            return Err(VaultError::InsufficientBalance);
+        }
+
+        let key = DataKey::Reservation(contract_id);
+        let mut reservation = match env.storage().persistent().get::<_, Reservation>(&key) {
+            Some(existing) if existing.asset == asset => existing,
+            // Switching assets is only possible once the old reservation is fully settled.
+            Some(existing) if existing.reserved > existing.spent => return Err(VaultError::ReservationAssetMismatch),
+            _ => Reservation { asset: asset.clone(), reserved: 0, spent: 0 },
+        };
+        reservation.reserved += amount;
+        env.storage().persistent().set(&key, &reservation);
+        Self::adjust_total_reserved(&env, &asset, amount);
+
+        env.events().publish((RESERVED, contract_id), (asset, amount));
+        Ok(())
+    }
+
+    /// Pays out of a contract's reservation.
+    pub fn pay_reserved(env: Env, contract_id: u32, to: Address, amount: i128) -> Result<(), VaultError> {
+        Self::check_lifecycle(&env)?;
+
+        if Self::is_paused(&env) {
+            return Err(VaultError::ContractPaused);
+        }
+
+        let payments = Vec::from_array(&env, [Payment { recipient: to, amount }]);
+        Self::pay_out_of_reservation(&env, contract_id, None, &payments)?;
+        Ok(())
+    }
+
+    /// Pays several recipients out of a contract's reservation in one call.
+    /// Either every payment is made or none is.
+    pub fn pay_reserved_split(env: Env, contract_id: u32, payments: Vec<Payment>) -> Result<(), VaultError> {
+        Self::check_lifecycle(&env)?;
+
+        if Self::is_paused(&env) {
+            return Err(VaultError::ContractPaused);
+        }
+
+        Self::pay_out_of_reservation(&env, contract_id, None, &payments)?;
+        Ok(())
+    }
+
+    /// Makes payouts out of the reservations of several contracts in `asset`
+    /// in one call, as a payroll run does. Either every payment is made or
+    /// none is.
+    pub fn pay_reserved_batch(env: Env, asset: Address, payouts: Vec<ReservedPayout>) -> Result<(), VaultError> {
+        Self::check_lifecycle(&env)?;
+
+        if Self::is_paused(&env) {
+            return Err(VaultError::ContractPaused);
+        }
+
+        if payouts.is_empty() {
+            return Err(VaultError::BatchEmptyList);
+        }
+
+        for payout in payouts.iter() {
+            Self::pay_out_of_reservation(&env, payout.contract_id, Some(&asset), &payout.payments)?;
+        }
+        Ok(())
+    }
+
+    /// Protocol fee the vault charges on a payout of `amount` in `asset`.
+    pub fn quote_fee(env: Env, asset: Address, amount: i128) -> i128 {
+        let Some(config) = Self::get_fee_config(env, asset) else {
+            return 0;
+        };
+        (amount * config.bps as i128 / BASIS_POINTS as i128).max(config.min_fee).min(amount)
+    }
+
+    /// Returns `amount` of a contract's unspent reservation to the vault's
+    /// unallocated liquidity.
+    pub fn release_reserved(env: Env, contract_id: u32, amount: i128) -> Result<(), VaultError> {
+        Self::check_lifecycle(&env)?;
+
+        if amount <= 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+
+        let key = DataKey::Reservation(contract_id);
+        let mut reservation: Reservation = env.storage().persistent().get(&key).ok_or(VaultError::ReservationNotFound)?;
+        if amount > reservation.reserved - reservation.spent {
+            return Err(VaultError::InsufficientReservedFunds);
+        }
+
+        reservation.reserved -= amount;
+        env.storage().persistent().set(&key, &reservation);
+        Self::adjust_total_reserved(&env, &reservation.asset, -amount);
+
+        env.events().publish((RELEASED, contract_id), (reservation.asset, amount));
+        Ok(())
+    }
+
+    pub fn get_reservation(env: Env, contract_id: u32) -> Option<Reservation> {
+        env.storage().persistent().get(&DataKey::Reservation(contract_id))
+    }
+
+    pub fn get_total_reserved(env: Env, asset: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::TotalReserved(asset)).unwrap_or(0)
+    }
+
     pub fn set_protocol_asset(env: Env, admin: Address, asset: Address) -> Result<(), VaultError> {
         Self::check_admin(&env, &admin)?;
         env.storage()
@@ -241,6 +375,56 @@ impl VaultContract {
         Ok(())
     }
   
+    // ====================================================================
+    // Protocol Fees
+    // ====================================================================
+
+    /// Sets the fee charged on batch payouts, invoices and contract payouts
+    /// in `asset`, once per payout. Batch payouts and invoices pay it on top
+    /// of the amount, out of unallocated liquidity. Contract payouts have it
+    /// withheld from the payout, so a fully reserved vault can still pay
+    /// them. A zero config turns fees off for the asset.
+    pub fn set_fee_config(env: Env, admin: Address, asset: Address, config: FeeConfig) -> Result<(), VaultError> {
+        Self::check_admin(&env, &admin)?;
+
+        if config.bps > BASIS_POINTS || config.min_fee < 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+
+        let key = DataKey::FeeConfig(asset);
+        if config.bps == 0 && config.min_fee == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &config);
+        }
+        Ok(())
+    }
+
+    pub fn get_fee_config(env: Env, asset: Address) -> Option<FeeConfig> {
+        env.storage().persistent().get(&DataKey::FeeConfig(asset))
+    }
+
+    /// Sends `amount` of the fees collected in `asset` to `to`.
+    pub fn withdraw_fees(env: Env, admin: Address, asset: Address, to: Address, amount: i128) -> Result<(), VaultError> {
+        Self::check_admin(&env, &admin)?;
+
+        if amount <= 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+
+        let key = DataKey::TotalFees(asset.clone());
+        let fees: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        if amount > fees {
+            return Err(VaultError::InsufficientBalance);
+        }
+
+        Self::internal_transfer(&env, &asset, &to, amount)?;
+        env.storage().persistent().set(&key, &(fees - amount));
+
+        env.events().publish((FEES_OUT, asset), (to, amount));
+        Ok(())
+    }
+
     // ====================================================================
     // Asset & Admin Management
     // ====================================================================
@@ -282,9 +466,11 @@ impl VaultContract {
         let locked: i128 = env.storage().persistent().get(&DataKey::TotalLocked(asset.clone())).unwrap_or(0);
         let fees: i128 = env.storage().persistent().get(&DataKey::TotalFees(asset.clone())).unwrap_or(0);
 
+        let reserved = Self::get_total_reserved(env.clone(), asset.clone());
+
         let client = token::Client::new(&env, &asset);
         let balance = client.balance(&env.current_contract_address());
-        let liquidity = balance.saturating_sub(locked);
+        let liquidity = balance.saturating_sub(locked).saturating_sub(reserved).saturating_sub(fees);
 
         TreasuryStats {
             total_deposits: deposits,
@@ -294,6 +480,30 @@ impl VaultContract {
         }
     }
 
+    // ====================================================================
+    // Upgrades
+    // ====================================================================
+
+    /// Replaces the vault's code, keeping its storage. Run `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), VaultError> {
+        upgrade_wasm(&env, &Self::get_admin_internal(&env)?, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Stamps storage with schema version 1. Nothing is moved: version 1 only
+    /// adds keys (reservations, fee settings, the lifecycle link) that read as
+    /// empty on older deployments.
+    pub fn migrate(env: Env) -> Result<u32, VaultError> {
+        let admin = Self::get_admin_internal(&env)?;
+        let from = pending_migration(&env, &admin, SCHEMA_VERSION).ok_or(VaultError::AlreadyMigrated)?;
+        complete_migration(&env, from, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
+
     // ====================================================================
     // Internal Helpers
     // ====================================================================
@@ -315,6 +525,113 @@ impl VaultContract {
         Ok(())
     }
 
+    fn check_lifecycle(env: &Env) -> Result<(), VaultError> {
+        let lifecycle: Address = env.storage()
+            .instance()
+            .get(&DataKey::Lifecycle)
+            .ok_or(VaultError::NotAuthorized)?;
+        lifecycle.require_auth();
+        Ok(())
+    }
+
+    /// Liquidity that is neither locked by deposits, reserved for contracts
+    /// nor owed to the protocol as fees.
+    fn available_balance(env: &Env, asset: &Address) -> i128 {
+        let client = token::Client::new(env, asset);
+        let balance = client.balance(&env.current_contract_address());
+        let locked: i128 = env.storage().persistent().get(&DataKey::TotalLocked(asset.clone())).unwrap_or(0);
+        let reserved: i128 = env.storage().persistent().get(&DataKey::TotalReserved(asset.clone())).unwrap_or(0);
+        let fees: i128 = env.storage().persistent().get(&DataKey::TotalFees(asset.clone())).unwrap_or(0);
+
+        balance - locked - reserved - fees
+    }
+
+    /// Books the protocol fee on a payout of `amount` out of the vault's
+    /// unallocated liquidity and returns it.
+    fn charge_fee(env: &Env, asset: &Address, amount: i128) -> Result<i128, VaultError> {
+        let fee = Self::quote_fee(env.clone(), asset.clone(), amount);
+        if fee == 0 {
+            return Ok(0);
+        }
+
+        if fee > Self::available_balance(env, asset) {
+            return Err(VaultError::InsufficientBalance);
+        }
+
+        Self::book_fee(env, asset, amount, fee);
+        Ok(fee)
+    }
+
+    /// Adds `fee`, charged on a payout of `amount`, to the fees owed to the
+    /// protocol.
+    fn book_fee(env: &Env, asset: &Address, amount: i128, fee: i128) {
+        let key = DataKey::TotalFees(asset.clone());
+        let fees: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(fees + fee));
+
+        env.events().publish((FEE, asset.clone()), (amount, fee));
+    }
+
+    /// Makes one payout out of a contract's reservation. The fee on its total
+    /// is withheld from the payments, starting with the last, so the payout
+    /// never spends more than its total. `asset`, when given, must match the
+    /// reservation's.
+    fn pay_out_of_reservation(
+        env: &Env,
+        contract_id: u32,
+        asset: Option<&Address>,
+        payments: &Vec<Payment>,
+    ) -> Result<(), VaultError> {
+        if payments.is_empty() {
+            return Err(VaultError::BatchEmptyList);
+        }
+
+        let mut total: i128 = 0;
+        for payment in payments.iter() {
+            if payment.amount <= 0 {
+                return Err(VaultError::InvalidAmount);
+            }
+            total += payment.amount;
+        }
+
+        let key = DataKey::Reservation(contract_id);
+        let mut reservation: Reservation = env.storage().persistent().get(&key).ok_or(VaultError::ReservationNotFound)?;
+        if asset.is_some_and(|asset| *asset != reservation.asset) {
+            return Err(VaultError::ReservationAssetMismatch);
+        }
+        if total > reservation.reserved - reservation.spent {
+            return Err(VaultError::InsufficientReservedFunds);
+        }
+
+        let fee = Self::quote_fee(env.clone(), reservation.asset.clone(), total);
+        if fee > 0 {
+            Self::book_fee(env, &reservation.asset, total, fee);
+        }
+
+        let mut withheld = fee;
+        for index in (0..payments.len()).rev() {
+            let payment = payments.get_unchecked(index);
+            let cut = withheld.min(payment.amount);
+            withheld -= cut;
+            if payment.amount > cut {
+                let paid = payment.amount - cut;
+                Self::internal_transfer(env, &reservation.asset, &payment.recipient, paid)?;
+                env.events().publish((PAYOUT, payment.recipient), (reservation.asset.clone(), paid));
+            }
+        }
+
+        reservation.spent += total;
+        env.storage().persistent().set(&key, &reservation);
+        Self::adjust_total_reserved(env, &reservation.asset, -total);
+        Ok(())
+    }
+
+    fn adjust_total_reserved(env: &Env, asset: &Address, delta: i128) {
+        let key = DataKey::TotalReserved(asset.clone());
+        let total: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(total + delta));
+    }
+
     fn is_whitelisted(env: &Env, asset: &Address) -> bool {
         env.storage().persistent().has(&DataKey::AssetWhitelist(asset.clone()))
     }
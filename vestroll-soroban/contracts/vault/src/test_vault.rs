@@ -1,6 +1,6 @@
 #![cfg(test)]
-use soroban_sdk::{testutils::Address as _, token, Address, Env};
-use vestroll_common::PayoutEntry;
+use soroban_sdk::{testutils::Address as _, token, Address, BytesN, Env};
+use vestroll_common::{FeeConfig, Payment, PayoutEntry, ReservedPayout, UpgradeKey, VaultError};
 
 use crate::{VaultContract, VaultContractClient};
 
@@ -202,6 +202,8 @@ fn test_pay_invoice_success() {
     let recipient = Address::generate(&env);
 
     let (token, token_address) = setup_funded_vault(&env, &client, &contract_id, &admin, 5000);
+    // Deposits stay locked; invoices are paid from unallocated liquidity.
+    token::StellarAssetClient::new(&env, &token_address).mint(&contract_id, &5000);
 
     let invoice_id = soroban_sdk::String::from_str(&env, "INV-2026");
     client.pay_invoice(&admin, &recipient, &1000, &token_address, &invoice_id);
@@ -222,4 +224,298 @@ fn test_pay_invoice_insufficient_funds() {
     let invoice_id = soroban_sdk::String::from_str(&env, "INV-2026");
     // Attempt to pay more than available
     client.pay_invoice(&admin, &recipient, &1000, &token_address, &invoice_id);
-}
\ No newline at end of file
+}
+// ── Reservation Tests ────────────────────────────────────────────────────────
+
+#[test]
+fn test_reservation_spend_and_release() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+    let lifecycle = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_client, token_admin_client, token_address) = create_token_contract(&env, &token_admin);
+
+    client.initialize(&admin, &token_address);
+    client.set_lifecycle_contract(&admin, &lifecycle);
+    token_admin_client.mint(&contract_id, &1000);
+
+    client.reserve(&7, &token_address, &600);
+    assert_eq!(client.get_total_reserved(&token_address), 600);
+    assert_eq!(client.get_treasury_stats(&token_address).total_liquidity, 400);
+
+    // Reserved funds are off limits to the unallocated pool.
+    assert_eq!(
+        client.try_withdraw_available(&recipient, &500, &token_address),
+        Err(Ok(VaultError::InsufficientBalance))
+    );
+    assert_eq!(
+        client.try_reserve(&8, &token_address, &500),
+        Err(Ok(VaultError::InsufficientBalance))
+    );
+
+    client.pay_reserved(&7, &recipient, &250);
+    assert_eq!(token_client.balance(&recipient), 250);
+    assert_eq!(
+        client.try_pay_reserved(&7, &recipient, &400),
+        Err(Ok(VaultError::InsufficientReservedFunds))
+    );
+
+    client.release_reserved(&7, &350);
+    let reservation = client.get_reservation(&7).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (250, 250));
+    assert_eq!(client.get_total_reserved(&token_address), 0);
+    assert_eq!(client.get_treasury_stats(&token_address).total_liquidity, 750);
+}
+
+#[test]
+fn test_reservation_requires_lifecycle() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+    let (_, token_admin_client, token_address) = create_token_contract(&env, &token_admin);
+
+    client.initialize(&admin, &token_address);
+    token_admin_client.mint(&contract_id, &1000);
+
+    assert_eq!(
+        client.try_reserve(&1, &token_address, &100),
+        Err(Ok(VaultError::NotAuthorized))
+    );
+}
+
+// ── Fee Tests ────────────────────────────────────────────────────────────────
+
+#[test]
+fn test_fees_accrue_on_payouts_and_invoices() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let r1 = Address::generate(&env);
+    let vendor = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let (token, token_address) = setup_funded_vault(&env, &client, &contract_id, &admin, 3000);
+    token::StellarAssetClient::new(&env, &token_address).mint(&contract_id, &1000);
+
+    assert_eq!(
+        client.try_set_fee_config(&admin, &token_address, &FeeConfig { bps: 10_001, min_fee: 0 }),
+        Err(Ok(VaultError::InvalidAmount))
+    );
+    // 1%, but at least 5 per payout.
+    client.set_fee_config(&admin, &token_address, &FeeConfig { bps: 100, min_fee: 5 });
+
+    let list = soroban_sdk::vec![
+        &env,
+        PayoutEntry { recipient: r1.clone(), amount: 2000, asset: token_address.clone() },
+    ];
+    client.execute_payouts(&contract_id, &list);
+    // Fees are charged on top, so recipients receive the full amount.
+    assert_eq!(token.balance(&r1), 2000);
+
+    let invoice_id = soroban_sdk::String::from_str(&env, "INV-2026");
+    client.pay_invoice(&admin, &vendor, &100, &token_address, &invoice_id);
+    assert_eq!(token.balance(&vendor), 100);
+
+    // Collected fees are not spendable liquidity.
+    let stats = client.get_treasury_stats(&token_address);
+    assert_eq!(stats.total_fees, 25);
+    assert_eq!(stats.total_liquidity, 875);
+
+    assert_eq!(
+        client.try_withdraw_fees(&admin, &token_address, &treasury, &26),
+        Err(Ok(VaultError::InsufficientBalance))
+    );
+    client.withdraw_fees(&admin, &token_address, &treasury, &25);
+    assert_eq!(token.balance(&treasury), 25);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 0);
+
+    // A zero config switches fees off.
+    client.set_fee_config(&admin, &token_address, &FeeConfig { bps: 0, min_fee: 0 });
+    assert_eq!(client.get_fee_config(&token_address), None);
+}
+
+#[test]
+fn test_fees_charged_on_reserved_payouts() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+    let lifecycle = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_client, token_admin_client, token_address) = create_token_contract(&env, &token_admin);
+
+    client.initialize(&admin, &token_address);
+    client.set_lifecycle_contract(&admin, &lifecycle);
+    client.set_fee_config(&admin, &token_address, &FeeConfig { bps: 250, min_fee: 0 });
+    token_admin_client.mint(&contract_id, &1000);
+
+    client.reserve(&7, &token_address, &600);
+    assert_eq!(client.quote_fee(&token_address, &400), 10);
+    client.pay_reserved(&7, &recipient, &400);
+
+    // The fee is withheld from the payout; unallocated liquidity is untouched.
+    assert_eq!(token_client.balance(&recipient), 390);
+    assert_eq!(client.get_reservation(&7).unwrap().spent, 400);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 10);
+    assert_eq!(client.get_treasury_stats(&token_address).total_liquidity, 400);
+}
+
+#[test]
+fn test_fee_charged_once_per_split_and_batched_payout() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+    let lifecycle = Address::generate(&env);
+    let employee = Address::generate(&env);
+    let landlord = Address::generate(&env);
+    let (token_client, token_admin_client, token_address) = create_token_contract(&env, &token_admin);
+
+    client.initialize(&admin, &token_address);
+    client.set_lifecycle_contract(&admin, &lifecycle);
+    // 1%, but at least 5 per payout.
+    client.set_fee_config(&admin, &token_address, &FeeConfig { bps: 100, min_fee: 5 });
+    token_admin_client.mint(&contract_id, &2000);
+    client.reserve(&1, &token_address, &1000);
+    client.reserve(&2, &token_address, &500);
+
+    // A payout split three ways is still one payout: 1% of 600, not 3
+    // minimums, withheld from the last payment.
+    let split = soroban_sdk::vec![
+        &env,
+        Payment { recipient: employee.clone(), amount: 500 },
+        Payment { recipient: landlord.clone(), amount: 50 },
+        Payment { recipient: landlord.clone(), amount: 50 },
+    ];
+    client.pay_reserved_split(&1, &split);
+    assert_eq!(token_client.balance(&employee), 500);
+    assert_eq!(token_client.balance(&landlord), 94);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 6);
+
+    let payouts = soroban_sdk::vec![
+        &env,
+        ReservedPayout {
+            contract_id: 1,
+            payments: soroban_sdk::vec![&env, Payment { recipient: employee.clone(), amount: 100 }],
+        },
+        ReservedPayout {
+            contract_id: 2,
+            payments: soroban_sdk::vec![&env, Payment { recipient: landlord.clone(), amount: 200 }],
+        },
+    ];
+    client.pay_reserved_batch(&token_address, &payouts);
+    assert_eq!(token_client.balance(&employee), 595);
+    assert_eq!(token_client.balance(&landlord), 289);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 16);
+    assert_eq!(client.get_reservation(&1).unwrap().spent, 700);
+    assert_eq!(client.get_reservation(&2).unwrap().spent, 200);
+}
+
+#[test]
+fn test_fee_withheld_when_vault_fully_reserved() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+    let lifecycle = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let remitter = Address::generate(&env);
+    let (token_client, token_admin_client, token_address) = create_token_contract(&env, &token_admin);
+
+    client.initialize(&admin, &token_address);
+    client.set_lifecycle_contract(&admin, &lifecycle);
+    client.set_fee_config(&admin, &token_address, &FeeConfig { bps: 100, min_fee: 5 });
+    token_admin_client.mint(&contract_id, &400);
+    client.reserve(&7, &token_address, &400);
+    assert_eq!(client.get_treasury_stats(&token_address).total_liquidity, 0);
+
+    // A last payment smaller than the fee is withheld entirely and the rest
+    // comes out of the payment before it.
+    let split = soroban_sdk::vec![
+        &env,
+        Payment { recipient: recipient.clone(), amount: 300 },
+        Payment { recipient: remitter.clone(), amount: 3 },
+    ];
+    client.pay_reserved_split(&7, &split);
+    assert_eq!(token_client.balance(&recipient), 298);
+    assert_eq!(token_client.balance(&remitter), 0);
+
+    client.pay_reserved(&7, &recipient, &97);
+    assert_eq!(token_client.balance(&recipient), 390);
+
+    // The whole reservation went out and the fees stayed behind in the vault.
+    let reservation = client.get_reservation(&7).unwrap();
+    assert_eq!((reservation.reserved, reservation.spent), (400, 400));
+    let stats = client.get_treasury_stats(&token_address);
+    assert_eq!((stats.total_fees, stats.total_liquidity), (10, 0));
+    assert_eq!(token_client.balance(&contract_id), 10);
+}
+
+#[test]
+fn test_fee_settings_restricted_to_admin() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let vendor = Address::generate(&env);
+
+    let (token, token_address) = setup_funded_vault(&env, &client, &contract_id, &admin, 3000);
+    token::StellarAssetClient::new(&env, &token_address).mint(&contract_id, &100);
+    let config = FeeConfig { bps: 100, min_fee: 5 };
+
+    assert_eq!(
+        client.try_set_fee_config(&stranger, &token_address, &config),
+        Err(Ok(VaultError::NotAuthorized))
+    );
+    assert_eq!(client.get_fee_config(&token_address), None);
+    client.set_fee_config(&admin, &token_address, &config);
+
+    // An invoice that would leave nothing for its fee is refused outright.
+    let invoice_id = soroban_sdk::String::from_str(&env, "INV-2026");
+    assert_eq!(
+        client.try_pay_invoice(&admin, &vendor, &100, &token_address, &invoice_id),
+        Err(Ok(VaultError::InsufficientBalance))
+    );
+    assert_eq!(token.balance(&vendor), 0);
+    client.pay_invoice(&admin, &vendor, &95, &token_address, &invoice_id);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 5);
+
+    assert_eq!(
+        client.try_withdraw_fees(&stranger, &token_address, &stranger, &5),
+        Err(Ok(VaultError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_withdraw_fees(&admin, &token_address, &admin, &0),
+        Err(Ok(VaultError::InvalidAmount))
+    );
+    assert_eq!(token.balance(&stranger), 0);
+    assert_eq!(client.get_treasury_stats(&token_address).total_fees, 5);
+}
+
+// ── Upgrade Tests ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_migrate_runs_once_per_version() {
+    let (env, client, contract_id) = create_test_env();
+    let admin = Address::generate(&env);
+    let (_, _, token_address) = create_token_contract(&env, &admin);
+    client.initialize(&admin, &token_address);
+
+    // Fresh deployments start at the current version.
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(VaultError::AlreadyMigrated)));
+
+    // A deployment from before versioning reports version 0 until migrated.
+    env.as_contract(&contract_id, || env.storage().instance().remove(&UpgradeKey::SchemaVersion));
+    assert_eq!(client.version(), 0);
+    assert_eq!(client.migrate(), 1);
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.try_migrate(), Err(Ok(VaultError::AlreadyMigrated)));
+}
+
+#[test]
+fn test_upgrade_requires_admin() {
+    let (env, client, _) = create_test_env();
+    let admin = Address::generate(&env);
+    let (_, _, token_address) = create_token_contract(&env, &admin);
+    client.initialize(&admin, &token_address);
+
+    env.set_auths(&[]);
+    assert!(client.try_upgrade(&BytesN::from_array(&env, &[0; 32])).is_err());
+}